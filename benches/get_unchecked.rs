@@ -0,0 +1,23 @@
+// Compares `get` with `get_unchecked` on an already calculated memo:
+//
+//     cargo bench --bench get_unchecked
+extern crate memo;
+
+use memo::ThreadsafeMemo;
+use std::hint::black_box;
+use std::time::Instant;
+
+fn main() {
+    let memo = ThreadsafeMemo::new(|| 212);
+    memo.get().unwrap();
+    let start = Instant::now();
+    for _ in 0..100_000_000 {
+        black_box(black_box(&memo).get().unwrap());
+    }
+    println!("get:           {:?}", start.elapsed());
+    let start = Instant::now();
+    for _ in 0..100_000_000 {
+        black_box(unsafe { black_box(&memo).get_unchecked() });
+    }
+    println!("get_unchecked: {:?}", start.elapsed());
+}
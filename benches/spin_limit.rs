@@ -0,0 +1,32 @@
+// Times a twelve-thread stampede on an instant calculation at a few spin
+// limits:
+//
+//     cargo bench --bench spin_limit
+extern crate memo;
+
+use memo::ThreadsafeMemo;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn stampede(limit: usize) -> Duration {
+    let memo = Arc::new(ThreadsafeMemo::with_spin_limit(|| 212, limit));
+    let start = Instant::now();
+    let threads: Vec<_> = (0..12).map(|_| {
+        let memo = memo.clone();
+        thread::spawn(move || {
+            assert_eq!(*memo.get().unwrap(), 212);
+        })
+    }).collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    start.elapsed()
+}
+
+fn main() {
+    for &limit in &[0, 64, 1024] {
+        let elapsed: Duration = (0..200).map(|_| stampede(limit)).sum();
+        println!("spin limit {:>5}: {:?}", limit, elapsed / 200);
+    }
+}
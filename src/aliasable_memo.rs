@@ -1,4 +1,11 @@
-use std::cell::{Cell, UnsafeCell};
+use core::cell::{Cell, UnsafeCell};
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::mem;
+#[cfg(feature = "std")]
+use std::cell::RefCell;
+#[cfg(feature = "std")]
+use std::error::Error;
 use memo::Memo;
 
 #[derive(Clone, Copy)]
@@ -6,11 +13,41 @@ enum CalculatingState {
     Uncalculated,
     Calculating,
     Calculated,
+    Poisoned,
+}
+
+// Leaves the memo poisoned unless disarmed by picking another destination,
+// so an unwinding closure can't strand it in `Calculating`.
+struct Finish<'a> {
+    destination_state: CalculatingState,
+    state: &'a Cell<CalculatingState>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReentrancyError;
+
+// The memos with cycle detection that this thread is calculating, innermost
+// last, identified by address.
+#[cfg(feature = "std")]
+thread_local! {
+    static CALCULATING: RefCell<Vec<(usize, &'static str)>> = const { RefCell::new(Vec::new()) };
+}
+
+// Pops its memo off `CALCULATING` even if the closure unwinds.
+#[cfg(feature = "std")]
+struct CycleFrame;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg(feature = "std")]
+pub struct CycleError {
+    path: Vec<&'static str>,
 }
 
 pub struct AliasableMemo<T, F: FnOnce() -> T> {
     calculating_state: Cell<CalculatingState>,
     memo: UnsafeCell<Memo<T, F>>,
+    #[cfg(all(feature = "std", debug_assertions))]
+    cycle_name: Option<&'static str>,
 }
 
 impl<T, F: FnOnce() -> T> AliasableMemo<T, F> {
@@ -18,6 +55,33 @@ impl<T, F: FnOnce() -> T> AliasableMemo<T, F> {
         AliasableMemo {
             calculating_state: Cell::new(CalculatingState::Uncalculated),
             memo: UnsafeCell::new(Memo::new(func)),
+            #[cfg(all(feature = "std", debug_assertions))]
+            cycle_name: None,
+        }
+    }
+
+    // Re-entering a memo that is calculating always fails, but only memos
+    // built this way are named in the `CycleError` describing the loop. The
+    // names are a debugging aid, so release builds drop them and report an
+    // empty path.
+    #[cfg(feature = "std")]
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    pub fn with_cycle_detection(func: F, name: &'static str) -> AliasableMemo<T, F> {
+        AliasableMemo {
+            calculating_state: Cell::new(CalculatingState::Uncalculated),
+            memo: UnsafeCell::new(Memo::new(func)),
+            #[cfg(debug_assertions)]
+            cycle_name: Some(name),
+        }
+    }
+
+    // Only `get_or_compute` and `set` can fill an empty memo; `get` panics.
+    pub fn empty() -> AliasableMemo<T, F> {
+        AliasableMemo {
+            calculating_state: Cell::new(CalculatingState::Uncalculated),
+            memo: UnsafeCell::new(Memo::empty()),
+            #[cfg(all(feature = "std", debug_assertions))]
+            cycle_name: None,
         }
     }
 
@@ -25,45 +89,558 @@ impl<T, F: FnOnce() -> T> AliasableMemo<T, F> {
         AliasableMemo {
             calculating_state: Cell::new(CalculatingState::Calculated),
             memo: UnsafeCell::new(Memo::with_value(value)),
+            #[cfg(all(feature = "std", debug_assertions))]
+            cycle_name: None,
         }
     }
 }
 
-impl<'a, T, F: FnOnce() -> T> AliasableMemo<T, F> {
+impl<T, F: FnOnce() -> T> AliasableMemo<T, F> {
+    // Panics if an earlier calculation panicked.
     pub fn get(&self) -> &T {
+        match self.try_get_calculating() {
+            Ok(v) => v,
+            Err(ReentrancyError) => self.reentered(),
+        }
+    }
+
+    // Runs `g` instead of the construction-time closure, which is dropped.
+    pub fn get_or_compute<G: FnOnce() -> T>(&self, g: G) -> &T {
+        match self.try_calculate(|memo| &*memo.get_or_insert_with(g)) {
+            Ok(v) => v,
+            Err(ReentrancyError) => self.reentered(),
+        }
+    }
+
+    fn reentered(&self) -> ! {
+        #[cfg(feature = "std")]
+        if self.cycle_name().is_some() {
+            panic!("{}!", self.cycle());
+        }
+        panic!("AliasableMemo's callback tried to access its own result!")
+    }
+
+    #[cfg(feature = "std")]
+    pub fn try_get_acyclic(&self) -> Result<&T, CycleError> {
+        self.try_get_calculating().map_err(|ReentrancyError| self.cycle())
+    }
+
+    // Memos without cycle detection aren't on the stack, so the path is empty
+    // if this is one of them.
+    #[cfg(feature = "std")]
+    fn cycle(&self) -> CycleError {
+        let name = match self.cycle_name() {
+            Some(name) => name,
+            None => return CycleError { path: Vec::new() },
+        };
+        let id = self as *const AliasableMemo<T, F> as usize;
+        CALCULATING.with(|stack| {
+            let stack = stack.borrow();
+            let start = stack.iter().position(|&(other, _)| other == id).unwrap_or(stack.len());
+            let mut path: Vec<_> = stack[start..].iter().map(|&(_, name)| name).collect();
+            path.push(name);
+            CycleError { path }
+        })
+    }
+
+    #[cfg(all(feature = "std", debug_assertions))]
+    fn cycle_name(&self) -> Option<&'static str> {
+        self.cycle_name
+    }
+
+    #[cfg(all(feature = "std", not(debug_assertions)))]
+    fn cycle_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    pub fn try_get_calculating(&self) -> Result<&T, ReentrancyError> {
+        self.try_calculate(|memo| memo.get())
+    }
+
+    fn try_calculate<C>(&self, calculate: C) -> Result<&T, ReentrancyError>
+        where C: FnOnce(&mut Memo<T, F>) -> &T
+    {
         match self.try_get() {
-            Some(v) => v,
+            Some(v) => Ok(v),
             None => {
-                if let CalculatingState::Calculating = self.calculating_state.get() {
-                    panic!("AliasableMemo's callback tried to access its own result!");
+                match self.calculating_state.get() {
+                    CalculatingState::Calculating => return Err(ReentrancyError),
+                    CalculatingState::Poisoned => panic!("AliasableMemo was poisoned by a panicking closure!"),
+                    CalculatingState::Uncalculated | CalculatingState::Calculated => {  },
                 }
-                self.calculating_state.set(CalculatingState::Calculating);
-                let out = unsafe { (*self.memo.get()).get() };
-                self.calculating_state.set(CalculatingState::Calculated);
-                out
+                let mut finish = self.finish();
+                #[cfg(feature = "std")]
+                let _frame = self.cycle_name().map(|name| CycleFrame::push(self as *const AliasableMemo<T, F> as usize, name));
+                let out = calculate(unsafe { &mut *self.memo.get() });
+                finish.destination_state = CalculatingState::Calculated;
+                Ok(out)
             },
         }
     }
 
+    // Every path into `Calculated` stores the inner value first: `finish`
+    // only switches once the closure has returned, and the `&mut self`
+    // methods switch after writing.
     pub fn try_get(&self) -> Option<&T> {
         match self.calculating_state.get() {
-            CalculatingState::Uncalculated | CalculatingState::Calculating => None,
-            CalculatingState::Calculated => unsafe { (*self.memo.get()).try_get() },
+            CalculatingState::Uncalculated | CalculatingState::Calculating | CalculatingState::Poisoned => None,
+            CalculatingState::Calculated => {
+                let value = unsafe { (*self.memo.get()).try_get() };
+                debug_assert!(value.is_some(), "calculated AliasableMemo is missing its value!");
+                value
+            },
+        }
+    }
+
+    pub fn value(&self) -> Option<&T> {
+        self.try_get()
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        let out = self.memo.get_mut().get_mut();
+        self.calculating_state.set(CalculatingState::Calculated);
+        out
+    }
+
+    // Earlier calls to `get` may still be borrowing a calculated value, so it
+    // can only be overwritten through `&mut self`. Poisoned memos have no value
+    // to borrow and can be recovered this way.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        match self.calculating_state.get() {
+            CalculatingState::Uncalculated | CalculatingState::Poisoned => {
+                let mut finish = self.finish();
+                unsafe { (*self.memo.get()).set(value) };
+                finish.destination_state = CalculatingState::Calculated;
+                Ok(())
+            },
+            CalculatingState::Calculating | CalculatingState::Calculated => Err(value),
+        }
+    }
+
+    // A poisoned memo has no value that could still be borrowed, so it can be
+    // given a fresh closure through `&self`. The old memo may still hold an
+    // unrun closure, so it's only dropped once the new state is in place, in
+    // case its destructor reaches back into this memo.
+    pub fn unpoison(&self, func: F) -> bool {
+        match self.calculating_state.get() {
+            CalculatingState::Poisoned => {
+                let old = unsafe { mem::replace(&mut *self.memo.get(), Memo::new(func)) };
+                self.calculating_state.set(CalculatingState::Uncalculated);
+                drop(old);
+                true
+            },
+            CalculatingState::Uncalculated | CalculatingState::Calculating | CalculatingState::Calculated => false,
+        }
+    }
+
+    pub fn replace(&mut self, value: T) -> Option<T> {
+        let old = mem::replace(self.memo.get_mut(), Memo::with_value(value));
+        self.calculating_state.set(CalculatingState::Calculated);
+        old.try_take()
+    }
+
+    pub fn is_calculating(&self) -> bool {
+        matches!(self.calculating_state.get(), CalculatingState::Calculating)
+    }
+
+    pub fn is_calculated(&self) -> bool {
+        matches!(self.calculating_state.get(), CalculatingState::Calculated)
+    }
+
+    pub fn is_uncalculated(&self) -> bool {
+        matches!(self.calculating_state.get(), CalculatingState::Uncalculated)
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        matches!(self.calculating_state.get(), CalculatingState::Poisoned)
+    }
+
+    fn finish<'a>(&'a self) -> Finish<'a> {
+        self.calculating_state.set(CalculatingState::Calculating);
+        Finish {
+            destination_state: CalculatingState::Poisoned,
+            state: &self.calculating_state,
         }
     }
 
     pub fn take(self) -> T {
-        unsafe { self.memo.into_inner().take() }
+        self.memo.into_inner().take()
     }
 
     pub fn try_take(self) -> Option<T> {
-        unsafe { self.memo.into_inner().try_take() }
+        self.memo.into_inner().try_take()
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn into_memo(self) -> Memo<T, F> {
+        self.memo.into_inner()
+    }
+}
+
+impl<T, F: FnOnce() -> T> From<Memo<T, F>> for AliasableMemo<T, F> {
+    fn from(memo: Memo<T, F>) -> AliasableMemo<T, F> {
+        let calculating_state = if memo.is_calculated() {
+            CalculatingState::Calculated
+        } else if memo.is_poisoned() {
+            CalculatingState::Poisoned
+        } else {
+            CalculatingState::Uncalculated
+        };
+        AliasableMemo {
+            calculating_state: Cell::new(calculating_state),
+            memo: UnsafeCell::new(memo),
+            #[cfg(all(feature = "std", debug_assertions))]
+            cycle_name: None,
+        }
+    }
+}
+
+// Compares like `Memo`: only calculated values count, and nothing is forced.
+impl<T: PartialEq, F: FnOnce() -> T, G: FnOnce() -> T> PartialEq<AliasableMemo<T, G>> for AliasableMemo<T, F> {
+    fn eq(&self, other: &AliasableMemo<T, G>) -> bool {
+        self.try_get() == other.try_get()
+    }
+}
+
+impl<T: Eq, F: FnOnce() -> T> Eq for AliasableMemo<T, F> {  }
+
+// Like `Memo`, the hash changes once the memo is calculated.
+impl<T: Hash, F: FnOnce() -> T> Hash for AliasableMemo<T, F> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.try_get().hash(state);
+    }
+}
+
+impl<'a> Drop for Finish<'a> {
+    fn drop(&mut self) {
+        self.state.set(self.destination_state);
     }
 }
 
+#[cfg(feature = "std")]
+impl CycleFrame {
+    fn push(id: usize, name: &'static str) -> CycleFrame {
+        CALCULATING.with(|stack| stack.borrow_mut().push((id, name)));
+        CycleFrame
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for CycleFrame {
+    fn drop(&mut self) {
+        CALCULATING.with(|stack| stack.borrow_mut().pop());
+    }
+}
+
+#[cfg(feature = "std")]
+impl CycleError {
+    // Starts and ends with the memo that was re-entered.
+    pub fn path(&self) -> &[&'static str] {
+        &self.path
+    }
+}
+
+impl fmt::Display for ReentrancyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("AliasableMemo's callback tried to access its own result")
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for ReentrancyError {  }
+
+#[cfg(feature = "std")]
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("AliasableMemo cycle detected: ")?;
+        f.write_str(&self.path.join(" -> "))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for CycleError {  }
+
 #[cfg(test)]
 #[allow(unused_assignments)]
 mod tests {
+    mod eq {
+        use super::super::AliasableMemo;
+
+        #[test]
+        fn calculated() {
+            let a = AliasableMemo::new(|| 212);
+            let b = AliasableMemo::new(|| 212);
+            let c = AliasableMemo::new(|| 666);
+            a.get();
+            b.get();
+            c.get();
+            assert!(a == b);
+            assert!(a != c);
+        }
+
+        #[test]
+        fn mixed() {
+            let a = AliasableMemo::new(|| 212);
+            let b = AliasableMemo::new(|| 212);
+            a.get();
+            assert!(a != b);
+            assert!(b.is_uncalculated());
+        }
+    }
+
+    mod hash {
+        use super::super::AliasableMemo;
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        fn hash<H: Hash>(value: &H) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        #[test]
+        fn stable() {
+            let memo = AliasableMemo::new(|| 212);
+            assert_eq!(hash(&memo), hash(&memo));
+            let uncalculated = hash(&memo);
+            memo.get();
+            assert_eq!(hash(&memo), hash(&memo));
+            assert_ne!(hash(&memo), uncalculated);
+            assert_eq!(hash(&memo), hash(&AliasableMemo::<u32, fn() -> u32>::with_value(212)));
+        }
+    }
+
+    mod poison {
+        use super::super::AliasableMemo;
+        use std::cell::Cell;
+        use std::panic::{self, AssertUnwindSafe};
+
+        fn poisoned() -> AliasableMemo<u32, fn() -> u32> {
+            fn fail() -> u32 { panic!() }
+            let memo = AliasableMemo::new(fail as fn() -> u32);
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                memo.get();
+            })).unwrap_err();
+            memo
+        }
+
+        #[test]
+        fn state() {
+            let memo = poisoned();
+            assert!(memo.is_poisoned());
+            assert!(!memo.is_calculating());
+            assert!(!memo.is_calculated());
+            assert!(!memo.is_uncalculated());
+            assert!(memo.try_get().is_none());
+        }
+
+        #[test]
+        #[should_panic(expected = "poisoned")]
+        fn get() {
+            poisoned().get();
+        }
+
+        #[test]
+        fn unpoison() {
+            let memo = poisoned();
+            assert!(memo.unpoison(|| 212));
+            assert!(memo.is_uncalculated());
+            assert!(!memo.unpoison(|| 200));
+            assert_eq!(*memo.get(), 212);
+            assert!(!memo.unpoison(|| 200));
+            assert_eq!(*memo.get(), 212);
+        }
+
+        #[test]
+        fn unpoison_panics_again() {
+            fn fail() -> u32 { panic!() }
+            let memo = poisoned();
+            assert!(memo.unpoison(fail));
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                memo.get();
+            })).unwrap_err();
+            assert!(memo.is_poisoned());
+            assert!(!memo.is_calculating());
+            assert!(memo.unpoison(|| 212));
+            assert_eq!(*memo.get(), 212);
+        }
+
+        #[test]
+        fn unpoison_from_drop() {
+            type Probe = AliasableMemo<u32, Box<dyn FnOnce() -> u32>>;
+
+            // Tries to unpoison the memo again while the old closure drops.
+            struct Reenter {
+                slot: &'static Cell<Option<&'static Probe>>,
+                unpoisoned: &'static Cell<Option<bool>>,
+            }
+
+            impl Drop for Reenter {
+                fn drop(&mut self) {
+                    if let Some(memo) = self.slot.get() {
+                        self.unpoisoned.set(Some(memo.unpoison(Box::new(|| 200))));
+                    }
+                }
+            }
+
+            let slot = Box::leak(Box::new(Cell::new(None)));
+            let unpoisoned = Box::leak(Box::new(Cell::new(None)));
+            let reenter = Reenter {
+                slot,
+                unpoisoned,
+            };
+            let probe: Probe = AliasableMemo::new(Box::new(move || {
+                let _reenter = reenter;
+                666
+            }));
+            let memo: &'static Probe = Box::leak(Box::new(probe));
+            slot.set(Some(memo));
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                memo.get_or_compute(|| panic!());
+            })).unwrap_err();
+            assert!(memo.is_poisoned());
+            assert!(memo.unpoison(Box::new(|| 212)));
+            assert_eq!(unpoisoned.get(), Some(false));
+            assert_eq!(*memo.get(), 212);
+        }
+
+        #[test]
+        fn set() {
+            let memo = poisoned();
+            assert_eq!(memo.set(212), Ok(()));
+            assert!(!memo.is_poisoned());
+            assert_eq!(*memo.get(), 212);
+        }
+    }
+
+    mod cycle_detection {
+        use super::super::{AliasableMemo, CycleError, CALCULATING};
+        use std::cell::{Cell, RefCell};
+        use std::panic::{self, AssertUnwindSafe};
+
+        type Node = AliasableMemo<u32, Box<dyn FnOnce() -> u32>>;
+
+        // The memos in a cycle borrow each other, so the tests leak them.
+        fn leak<T>(value: T) -> &'static T {
+            Box::leak(Box::new(value))
+        }
+
+        #[test]
+        fn two_nodes() {
+            let error: &RefCell<Option<CycleError>> = leak(RefCell::new(None));
+            let slot: &Cell<Option<&Node>> = leak(Cell::new(None));
+            let b: &Node = leak(AliasableMemo::with_cycle_detection(Box::new(move || {
+                match slot.get().unwrap().try_get_acyclic() {
+                    Ok(&value) => value,
+                    Err(err) => {
+                        *error.borrow_mut() = Some(err);
+                        212
+                    },
+                }
+            }), "b"));
+            let a: &Node = leak(AliasableMemo::with_cycle_detection(Box::new(move || b.get() + 1), "a"));
+            slot.set(Some(a));
+            assert_eq!(a.try_get_acyclic().copied(), Ok(213));
+            let error = error.borrow_mut().take().unwrap();
+            #[cfg(debug_assertions)]
+            assert_eq!(error.path(), ["a", "b", "a"]);
+            #[cfg(debug_assertions)]
+            assert_eq!(error.to_string(), "AliasableMemo cycle detected: a -> b -> a");
+            #[cfg(not(debug_assertions))]
+            assert!(error.path().is_empty());
+            CALCULATING.with(|stack| assert!(stack.borrow().is_empty()));
+        }
+
+        #[test]
+        fn three_nodes() {
+            let slot: &Cell<Option<&Node>> = leak(Cell::new(None));
+            let c: &Node = leak(AliasableMemo::with_cycle_detection(Box::new(move || *slot.get().unwrap().get()), "c"));
+            let b: &Node = leak(AliasableMemo::with_cycle_detection(Box::new(move || *c.get()), "b"));
+            let a: &Node = leak(AliasableMemo::with_cycle_detection(Box::new(move || *b.get()), "a"));
+            slot.set(Some(b));
+            let err = panic::catch_unwind(AssertUnwindSafe(|| {
+                a.get();
+            })).unwrap_err();
+            #[cfg(debug_assertions)]
+            assert_eq!(err.downcast_ref::<String>().unwrap(), "AliasableMemo cycle detected: b -> c -> b!");
+            #[cfg(not(debug_assertions))]
+            assert_eq!(*err.downcast_ref::<&str>().unwrap(), "AliasableMemo's callback tried to access its own result!");
+            assert!(a.is_poisoned() && b.is_poisoned() && c.is_poisoned());
+            CALCULATING.with(|stack| assert!(stack.borrow().is_empty()));
+        }
+
+        #[test]
+        fn acyclic() {
+            let c = AliasableMemo::with_cycle_detection(|| 200, "c");
+            let b = AliasableMemo::with_cycle_detection(|| *c.get() + 10, "b");
+            let a = AliasableMemo::with_cycle_detection(|| *b.get() + *c.get() - 198, "a");
+            assert_eq!(a.try_get_acyclic(), Ok(&212));
+            CALCULATING.with(|stack| assert!(stack.borrow().is_empty()));
+        }
+
+        #[test]
+        fn undetected() {
+            let memo = AliasableMemo::new(|| 212);
+            assert!(memo.cycle().path().is_empty());
+        }
+    }
+
+    mod value {
+        use super::super::AliasableMemo;
+        use std::cell::Cell;
+        use std::panic::{self, AssertUnwindSafe};
+
+        type Probe = AliasableMemo<bool, Box<dyn FnOnce() -> bool>>;
+
+        #[test]
+        fn calculated() {
+            let memo = AliasableMemo::new(|| 212);
+            assert_eq!(memo.value(), None);
+            memo.get();
+            assert_eq!(memo.value(), Some(&212));
+        }
+
+        #[test]
+        fn calculating() {
+            let slot: &Cell<Option<&Probe>> = Box::leak(Box::new(Cell::new(None)));
+            let probe: Probe = AliasableMemo::new(Box::new(move || slot.get().unwrap().value().is_none()));
+            let memo: &Probe = Box::leak(Box::new(probe));
+            slot.set(Some(memo));
+            assert!(*memo.get());
+        }
+
+        #[test]
+        fn poisoned() {
+            let memo = AliasableMemo::new(|| -> u32 { panic!() });
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                memo.get();
+            })).unwrap_err();
+            assert_eq!(memo.value(), None);
+        }
+    }
+
+    mod from_memo {
+        use super::super::AliasableMemo;
+        use memo::Memo;
+
+        #[test]
+        fn uncalculated() {
+            let memo = AliasableMemo::from(Memo::new(|| 212));
+            assert!(memo.is_uncalculated());
+            assert_eq!(*memo.get(), 212);
+        }
+
+        #[test]
+        fn calculated() {
+            let mut memo = Memo::new(|| 212);
+            memo.get();
+            let memo = AliasableMemo::from(memo);
+            assert!(memo.is_calculated());
+            assert_eq!(memo.try_get(), Some(&212));
+        }
+    }
+
     mod new {
         use super::super::AliasableMemo;
 
@@ -119,6 +696,50 @@ mod tests {
             assert_eq!(times, 0);
         }
 
+        #[test]
+        fn get_mut() {
+            let mut times = 0;
+            {
+                let mut memo = AliasableMemo::new(|| {
+                    times += 1;
+                    vec![212]
+                });
+                memo.get_mut().push(213);
+                assert_eq!(*memo.try_get().unwrap(), vec![212, 213]);
+                assert_eq!(*memo.get(), vec![212, 213]);
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        fn set() {
+            let mut times = 0;
+            {
+                let memo = AliasableMemo::new(|| {
+                    times += 1;
+                    200
+                });
+                memo.set(212).unwrap();
+                assert_eq!(*memo.get(), 212);
+                assert_eq!(memo.set(213), Err(213));
+            }
+            assert_eq!(times, 0);
+        }
+
+        #[test]
+        fn replace() {
+            let mut times = 0;
+            {
+                let mut memo = AliasableMemo::new(|| {
+                    times += 1;
+                    200
+                });
+                assert!(memo.replace(212).is_none());
+                assert_eq!(*memo.get(), 212);
+            }
+            assert_eq!(times, 0);
+        }
+
         #[test]
         fn get_get() {
             let mut times = 0;
@@ -161,6 +782,36 @@ mod tests {
             assert_eq!(times, 1);
         }
 
+        #[test]
+        fn get_get_mut() {
+            let mut times = 0;
+            {
+                let mut memo = AliasableMemo::new(|| {
+                    times += 1;
+                    212 + times - 1
+                });
+                assert_eq!(*memo.get(), 212);
+                *memo.get_mut() += 1;
+                assert_eq!(*memo.get(), 213);
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        fn get_replace() {
+            let mut times = 0;
+            {
+                let mut memo = AliasableMemo::new(|| {
+                    times += 1;
+                    212 + times - 1
+                });
+                assert_eq!(*memo.get(), 212);
+                assert_eq!(memo.replace(213), Some(212));
+                assert_eq!(*memo.get(), 213);
+            }
+            assert_eq!(times, 1);
+        }
+
         #[test]
         fn get_try_take() {
             let mut times = 0;
@@ -176,6 +827,200 @@ mod tests {
         }
     }
 
+    mod invariant {
+        use super::super::{AliasableMemo, CalculatingState};
+        use memo::Memo;
+
+        #[test]
+        fn calculated_has_value() {
+            let memo = AliasableMemo::new(|| 212);
+            memo.get();
+            assert!(memo.is_calculated());
+            assert_eq!(memo.try_get(), Some(&212));
+
+            let memo = AliasableMemo::new(|| 200);
+            memo.set(212).unwrap();
+            assert_eq!(memo.try_get(), Some(&212));
+
+            let mut memo = AliasableMemo::new(|| 212);
+            memo.get_mut();
+            assert_eq!(memo.try_get(), Some(&212));
+
+            let mut memo = AliasableMemo::new(|| 200);
+            memo.replace(212);
+            assert_eq!(memo.try_get(), Some(&212));
+
+            let mut inner = Memo::new(|| 212);
+            inner.get();
+            assert_eq!(AliasableMemo::from(inner).try_get(), Some(&212));
+        }
+
+        #[test]
+        #[should_panic(expected = "missing its value")]
+        #[cfg(debug_assertions)]
+        fn broken() {
+            let memo = AliasableMemo::new(|| 212);
+            memo.calculating_state.set(CalculatingState::Calculated);
+            memo.try_get();
+        }
+    }
+
+    mod get_or_compute {
+        use super::super::AliasableMemo;
+        use std::cell::Cell;
+        use std::panic::{self, AssertUnwindSafe};
+
+        #[test]
+        fn get() {
+            let mut times = 0;
+            {
+                let memo: AliasableMemo<u32, fn() -> u32> = AliasableMemo::empty();
+                assert!(memo.is_uncalculated());
+                assert_eq!(*memo.get_or_compute(|| {
+                    times += 1;
+                    212
+                }), 212);
+                assert!(memo.is_calculated());
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        fn get_get() {
+            let times = Cell::new(0);
+            let compute = || {
+                times.set(times.get() + 1);
+                212 + times.get() - 1
+            };
+            let memo: AliasableMemo<u32, fn() -> u32> = AliasableMemo::empty();
+            let first = memo.get_or_compute(compute);
+            assert_eq!(*memo.get_or_compute(compute), 212);
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(*first, 212);
+            assert_eq!(times.get(), 1);
+        }
+
+        #[test]
+        fn ignores_closure() {
+            let mut times = 0;
+            {
+                let memo = AliasableMemo::new(|| {
+                    times += 1;
+                    200
+                });
+                assert_eq!(*memo.get_or_compute(|| 212), 212);
+                assert_eq!(*memo.get(), 212);
+            }
+            assert_eq!(times, 0);
+        }
+
+        #[test]
+        fn calculated() {
+            let memo = AliasableMemo::new(|| 212);
+            memo.get();
+            assert_eq!(*memo.get_or_compute(|| 200), 212);
+        }
+
+        #[test]
+        #[should_panic(expected = "tried to access its own result")]
+        fn reentrancy() {
+            let memo: AliasableMemo<u32, fn() -> u32> = AliasableMemo::empty();
+            memo.get_or_compute(|| *memo.get_or_compute(|| 212));
+        }
+
+        #[test]
+        fn poisoned() {
+            let memo: AliasableMemo<u32, fn() -> u32> = AliasableMemo::empty();
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                memo.get_or_compute(|| panic!());
+            })).unwrap_err();
+            assert!(memo.is_poisoned());
+        }
+
+        #[test]
+        #[should_panic(expected = "neither a value nor a closure")]
+        fn empty_get() {
+            let memo: AliasableMemo<u32, fn() -> u32> = AliasableMemo::empty();
+            memo.get();
+        }
+    }
+
+    mod reentrancy {
+        use super::super::{AliasableMemo, ReentrancyError};
+        use std::cell::Cell;
+        use std::ptr;
+
+        type SelfMemo = AliasableMemo<u32, fn() -> u32>;
+
+        thread_local! {
+            static MEMO: Cell<*const SelfMemo> = const { Cell::new(ptr::null()) };
+            static INNER: Cell<Option<Result<u32, ReentrancyError>>> = const { Cell::new(None) };
+            static CALCULATING: Cell<bool> = const { Cell::new(false) };
+            static SET: Cell<Option<Result<(), u32>>> = const { Cell::new(None) };
+        }
+
+        fn memo() -> &'static SelfMemo {
+            unsafe { &*MEMO.with(Cell::get) }
+        }
+
+        fn try_reenter() -> u32 {
+            INNER.with(|inner| inner.set(Some(memo().try_get_calculating().copied())));
+            212
+        }
+
+        fn check_calculating() -> u32 {
+            CALCULATING.with(|calculating| calculating.set(memo().is_calculating()));
+            212
+        }
+
+        fn set_calculating() -> u32 {
+            SET.with(|set| set.set(Some(memo().set(200))));
+            212
+        }
+
+        fn reenter() -> u32 {
+            *memo().get()
+        }
+
+        #[test]
+        fn try_get_calculating() {
+            let memo = AliasableMemo::new(try_reenter as fn() -> u32);
+            MEMO.with(|m| m.set(&memo));
+            assert_eq!(*memo.try_get_calculating().unwrap(), 212);
+            assert_eq!(INNER.with(Cell::get), Some(Err(ReentrancyError)));
+            assert_eq!(*memo.try_get_calculating().unwrap(), 212);
+        }
+
+        #[test]
+        fn is_calculating() {
+            let memo = AliasableMemo::new(check_calculating as fn() -> u32);
+            MEMO.with(|m| m.set(&memo));
+            assert!(memo.is_uncalculated());
+            assert!(!memo.is_calculating());
+            memo.get();
+            assert!(CALCULATING.with(Cell::get));
+            assert!(memo.is_calculated());
+            assert!(!memo.is_calculating());
+            assert!(!memo.is_uncalculated());
+        }
+
+        #[test]
+        fn set() {
+            let memo = AliasableMemo::new(set_calculating as fn() -> u32);
+            MEMO.with(|m| m.set(&memo));
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(SET.with(Cell::get), Some(Err(200)));
+        }
+
+        #[test]
+        #[should_panic(expected = "tried to access its own result")]
+        fn get() {
+            let memo = AliasableMemo::new(reenter as fn() -> u32);
+            MEMO.with(|m| m.set(&memo));
+            memo.get();
+        }
+    }
+
     mod with_value {
         use super::super::AliasableMemo;
 
@@ -200,6 +1045,13 @@ mod tests {
             assert_eq!(memo.take(), 212);
         }
 
+        #[test]
+        fn get_mut() {
+            let mut memo = AliasableMemo::new(|| { 200 });
+            memo = AliasableMemo::with_value(212);
+            assert_eq!(*memo.get_mut(), 212);
+        }
+
         #[test]
         fn try_take() {
             let mut memo = AliasableMemo::new(|| { 200 });
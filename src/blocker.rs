@@ -0,0 +1,45 @@
+use sync::thread::{self, Thread};
+use std::time::Duration;
+
+// `block` may return spuriously; callers always recheck their condition. The
+// queue keeps its own clone of the waiter's blocker so that `unblock` never
+// touches memory the waiter might already have released.
+pub trait Blocker: Clone {
+    fn current() -> Self;
+    fn block(&self);
+    fn unblock(&self);
+
+    // Blockers that can sleep indefinitely should override this, or waiters
+    // with a deadline may oversleep it.
+    fn block_timeout(&self, timeout: Duration) {
+        let _ = timeout;
+        self.block();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ParkBlocker {
+    thread: Thread,
+}
+
+impl Blocker for ParkBlocker {
+    fn current() -> ParkBlocker {
+        ParkBlocker {
+            thread: thread::current(),
+        }
+    }
+
+    fn block(&self) {
+        thread::park();
+    }
+
+    fn unblock(&self) {
+        self.thread.unpark();
+    }
+
+    // loom doesn't model time, so a timed-out wait is just a spurious wakeup.
+    #[cfg(not(feature = "loom"))]
+    fn block_timeout(&self, timeout: Duration) {
+        thread::park_timeout(timeout);
+    }
+}
@@ -0,0 +1,62 @@
+use core::cmp;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use aliasable_memo::AliasableMemo;
+
+pub struct ChunkedMemo<T> {
+    source: AliasableMemo<Vec<T>, Box<dyn FnOnce() -> Vec<T>>>,
+}
+
+impl<T> ChunkedMemo<T> {
+    pub fn new<F: FnOnce() -> Vec<T> + 'static>(func: F) -> ChunkedMemo<T> {
+        ChunkedMemo {
+            source: AliasableMemo::new(Box::new(func)),
+        }
+    }
+}
+
+impl<T> ChunkedMemo<T> {
+    pub fn chunk(&self, index: usize, chunk_size: usize) -> &[T] {
+        assert!(chunk_size != 0, "ChunkedMemo's chunk size must be non-zero!");
+        let source = self.source.get();
+        let start = cmp::min(index.saturating_mul(chunk_size), source.len());
+        let end = cmp::min(start.saturating_add(chunk_size), source.len());
+        &source[start..end]
+    }
+
+    pub fn try_get(&self) -> Option<&[T]> {
+        self.source.try_get().map(|source| &source[..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChunkedMemo;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn chunk() {
+        let times = Rc::new(Cell::new(0));
+        let memo = {
+            let times = times.clone();
+            ChunkedMemo::new(move || {
+                times.set(times.get() + 1);
+                (0..10).collect()
+            })
+        };
+        assert!(memo.try_get().is_none());
+        assert_eq!(memo.chunk(1, 4), &[4, 5, 6, 7]);
+        assert_eq!(memo.chunk(0, 4), &[0, 1, 2, 3]);
+        assert_eq!(memo.chunk(2, 4), &[8, 9]);
+        assert!(memo.chunk(3, 4).is_empty());
+        assert_eq!(times.get(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_size() {
+        let memo = ChunkedMemo::new(|| vec![212]);
+        memo.chunk(0, 0);
+    }
+}
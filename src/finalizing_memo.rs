@@ -0,0 +1,78 @@
+use memo::Memo;
+
+pub struct FinalizingMemo<T, F: FnOnce() -> T, D: FnOnce(T)> {
+    memo: Option<Memo<T, F>>,
+    finalizer: Option<D>,
+}
+
+impl<T, F: FnOnce() -> T> Memo<T, F> {
+    pub fn new_with_finalizer<D: FnOnce(T)>(func: F, finalizer: D) -> FinalizingMemo<T, F, D> {
+        FinalizingMemo {
+            memo: Some(Memo::new(func)),
+            finalizer: Some(finalizer),
+        }
+    }
+}
+
+impl<T, F: FnOnce() -> T, D: FnOnce(T)> FinalizingMemo<T, F, D> {
+    pub fn get(&mut self) -> &T {
+        self.memo.as_mut().unwrap().get()
+    }
+
+    pub fn try_get(&self) -> Option<&T> {
+        self.memo.as_ref().unwrap().try_get()
+    }
+
+    pub fn take(mut self) -> T {
+        self.finalizer = None;
+        self.memo.take().unwrap().take()
+    }
+}
+
+impl<T, F: FnOnce() -> T, D: FnOnce(T)> Drop for FinalizingMemo<T, F, D> {
+    fn drop(&mut self) {
+        if let (Some(memo), Some(finalizer)) = (self.memo.take(), self.finalizer.take()) {
+            if let Some(value) = memo.try_take() {
+                finalizer(value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use memo::Memo;
+    use std::cell::Cell;
+
+    #[test]
+    fn calculated() {
+        let finalized = Cell::new(None);
+        {
+            let mut memo = Memo::new_with_finalizer(|| 212, |value| finalized.set(Some(value)));
+            assert_eq!(*memo.get(), 212);
+            assert!(finalized.get().is_none());
+        }
+        assert_eq!(finalized.get(), Some(212));
+    }
+
+    #[test]
+    fn uncalculated() {
+        let finalized = Cell::new(None);
+        {
+            let memo = Memo::new_with_finalizer(|| 212, |value| finalized.set(Some(value)));
+            assert!(memo.try_get().is_none());
+        }
+        assert!(finalized.get().is_none());
+    }
+
+    #[test]
+    fn take() {
+        let finalized = Cell::new(None);
+        {
+            let mut memo = Memo::new_with_finalizer(|| 212, |value| finalized.set(Some(value)));
+            memo.get();
+            assert_eq!(memo.take(), 212);
+        }
+        assert!(finalized.get().is_none());
+    }
+}
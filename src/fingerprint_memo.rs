@@ -0,0 +1,102 @@
+pub struct FingerprintMemo<T, K: Eq, F: FnMut() -> T> {
+    func: F,
+    key_fn: fn(&T) -> K,
+    value: Option<(T, K)>,
+    changed: bool,
+}
+
+impl<T, K: Eq, F: FnMut() -> T> FingerprintMemo<T, K, F> {
+    pub fn new(func: F, key_fn: fn(&T) -> K) -> FingerprintMemo<T, K, F> {
+        FingerprintMemo {
+            func,
+            key_fn,
+            value: None,
+            changed: false,
+        }
+    }
+}
+
+impl<T, K: Eq, F: FnMut() -> T> FingerprintMemo<T, K, F> {
+    pub fn get(&mut self) -> &T {
+        if self.value.is_none() {
+            self.recompute();
+        }
+        &self.value.as_ref().unwrap().0
+    }
+
+    pub fn try_get(&self) -> Option<&T> {
+        self.value.as_ref().map(|pair| &pair.0)
+    }
+
+    pub fn recompute(&mut self) -> &T {
+        let value = (self.func)();
+        let key = (self.key_fn)(&value);
+        self.changed = match self.value {
+            Some((_, ref old_key)) => *old_key != key,
+            None => true,
+        };
+        if self.changed {
+            self.value = Some((value, key));
+        }
+        &self.value.as_ref().unwrap().0
+    }
+
+    pub fn changed_since_last(&self) -> bool {
+        self.changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FingerprintMemo;
+
+    fn key(value: &(u32, u32)) -> u32 {
+        value.0
+    }
+
+    #[test]
+    fn get() {
+        let mut times = 0;
+        {
+            let mut memo = FingerprintMemo::new(|| {
+                times += 1;
+                (212, times)
+            }, key);
+            assert!(memo.try_get().is_none());
+            assert_eq!(*memo.get(), (212, 1));
+            assert_eq!(*memo.get(), (212, 1));
+            assert!(memo.changed_since_last());
+        }
+        assert_eq!(times, 1);
+    }
+
+    #[test]
+    fn recompute_unchanged() {
+        let mut times = 0;
+        {
+            let mut memo = FingerprintMemo::new(|| {
+                times += 1;
+                (212, times)
+            }, key);
+            assert_eq!(*memo.get(), (212, 1));
+            assert_eq!(*memo.recompute(), (212, 1));
+            assert!(!memo.changed_since_last());
+        }
+        assert_eq!(times, 2);
+    }
+
+    #[test]
+    fn recompute_changed() {
+        let mut times = 0;
+        {
+            let mut memo = FingerprintMemo::new(|| {
+                times += 1;
+                (211 + times, times)
+            }, key);
+            assert_eq!(*memo.get(), (212, 1));
+            assert_eq!(*memo.recompute(), (213, 2));
+            assert!(memo.changed_since_last());
+        }
+        assert_eq!(times, 2);
+    }
+}
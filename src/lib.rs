@@ -1,10 +1,85 @@
-#![cfg_attr(feature = "unstable", feature(atomic_access))]
-#![cfg_attr(test, feature(fn_traits, unboxed_closures))]
+#![cfg_attr(not(feature = "std"), no_std)]
+#![allow(clippy::result_unit_err)]
 
+// `Memo`, `AliasableMemo` and the other single-threaded memos only need
+// `core`; `ChunkedMemo`, `MemoCell` and `try_fold_memos` need `alloc`, and
+// everything that blocks, times or hashes needs `std`. The unit tests assume
+// `std`; tests/no_std.rs covers the rest without it.
+#[cfg(feature = "std")]
+extern crate core;
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "loom")]
+extern crate loom;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+
+#[macro_use]
+mod macros;
 mod memo;
 mod aliasable_memo;
+#[cfg(feature = "std")]
+mod sync;
+#[cfg(feature = "std")]
+mod blocker;
+#[cfg(feature = "std")]
 mod threadsafe_memo;
+#[cfg(feature = "std")]
+mod threadsafe_try_memo;
+#[cfg(feature = "std")]
+mod threadsafe_memo_cell;
+#[cfg(feature = "std")]
+mod tracked_threadsafe_memo;
+mod fingerprint_memo;
+mod finalizing_memo;
+#[cfg(feature = "std")]
+mod logging_memo;
+mod recomputing_memo;
+mod resettable_memo;
+mod ref_cell_memo;
+#[cfg(feature = "alloc")]
+mod chunked_memo;
+#[cfg(feature = "alloc")]
+mod memo_cell;
+#[cfg(feature = "std")]
+mod memo_map;
+#[cfg(feature = "std")]
+mod threadsafe_memo_map;
+mod traits;
+#[cfg(feature = "serde")]
+mod serde_impls;
 
-pub use memo::Memo;
-pub use aliasable_memo::AliasableMemo;
-pub use threadsafe_memo::ThreadsafeMemo;
+pub use memo::{Memo, CalculatedMemo, PoisonError};
+#[cfg(feature = "alloc")]
+pub use memo::try_fold_memos;
+pub use aliasable_memo::{AliasableMemo, ReentrancyError};
+#[cfg(feature = "std")]
+pub use aliasable_memo::CycleError;
+#[cfg(feature = "std")]
+pub use blocker::{Blocker, ParkBlocker};
+#[cfg(feature = "std")]
+pub use threadsafe_memo::{ThreadsafeMemo, ComputeGuard, MemoState, TryError, TryGetResult, WaitResult, snapshot_values, warm_all};
+#[cfg(feature = "std")]
+pub use threadsafe_try_memo::ThreadsafeTryMemo;
+#[cfg(feature = "std")]
+pub use threadsafe_memo_cell::ThreadsafeMemoCell;
+#[cfg(feature = "std")]
+pub use tracked_threadsafe_memo::TrackedThreadsafeMemo;
+pub use fingerprint_memo::FingerprintMemo;
+pub use finalizing_memo::FinalizingMemo;
+#[cfg(feature = "std")]
+pub use logging_memo::{LoggingMemo, LogEvent, LogEventKind};
+pub use recomputing_memo::RecomputingMemo;
+pub use resettable_memo::ResettableMemo;
+pub use ref_cell_memo::RefCellMemo;
+#[cfg(feature = "alloc")]
+pub use chunked_memo::ChunkedMemo;
+#[cfg(feature = "alloc")]
+pub use memo_cell::MemoCell;
+#[cfg(feature = "std")]
+pub use memo_map::MemoMap;
+#[cfg(feature = "std")]
+pub use threadsafe_memo_map::ThreadsafeMemoMap;
+pub use traits::{Lazy, LazyShared};
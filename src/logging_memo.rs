@@ -0,0 +1,93 @@
+use std::time::{Duration, Instant};
+use memo::Memo;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogEventKind {
+    Miss,
+    Hit,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LogEvent<'a> {
+    pub key: &'a str,
+    pub kind: LogEventKind,
+    pub elapsed: Duration,
+}
+
+pub struct LoggingMemo<T, F: FnOnce() -> T, S: Fn(&LogEvent)> {
+    key: String,
+    memo: Memo<T, F>,
+    sink: S,
+}
+
+impl<T, F: FnOnce() -> T, S: Fn(&LogEvent)> LoggingMemo<T, F, S> {
+    pub fn new(key: &str, func: F, sink: S) -> LoggingMemo<T, F, S> {
+        LoggingMemo {
+            key: key.to_owned(),
+            memo: Memo::new(func),
+            sink,
+        }
+    }
+}
+
+impl<T, F: FnOnce() -> T, S: Fn(&LogEvent)> LoggingMemo<T, F, S> {
+    pub fn get(&mut self) -> &T {
+        let kind = if self.memo.is_calculated() {
+            LogEventKind::Hit
+        } else {
+            LogEventKind::Miss
+        };
+        let start = Instant::now();
+        self.memo.get();
+        (self.sink)(&LogEvent {
+            key: &self.key,
+            kind,
+            elapsed: start.elapsed(),
+        });
+        self.memo.try_get().unwrap()
+    }
+
+    pub fn try_get(&self) -> Option<&T> {
+        self.memo.try_get()
+    }
+
+    pub fn take(self) -> T {
+        self.memo.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LoggingMemo, LogEventKind};
+    use std::cell::RefCell;
+
+    #[test]
+    fn get() {
+        let events = RefCell::new(Vec::new());
+        let mut times = 0;
+        {
+            let mut memo = LoggingMemo::new("answer", || {
+                times += 1;
+                212
+            }, |event| {
+                assert_eq!(event.key, "answer");
+                events.borrow_mut().push(event.kind);
+            });
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(*memo.get(), 212);
+        }
+        assert_eq!(times, 1);
+        assert_eq!(*events.borrow(), vec![LogEventKind::Miss, LogEventKind::Hit, LogEventKind::Hit]);
+    }
+
+    #[test]
+    fn try_get() {
+        let events = RefCell::new(Vec::new());
+        let memo = LoggingMemo::new("answer", || 212, |event| {
+            events.borrow_mut().push(event.kind);
+        });
+        assert!(memo.try_get().is_none());
+        assert!(events.borrow().is_empty());
+    }
+}
@@ -0,0 +1,115 @@
+#[macro_export]
+macro_rules! lazy_chain {
+    (@compose $source:expr) => {
+        $source
+    };
+    (@compose $source:expr, $stage:expr $(, $rest:expr)*) => {
+        $crate::lazy_chain!(@compose {
+            let source = $source;
+            let stage = $stage;
+            move || stage(source())
+        } $(, $rest)*)
+    };
+    ($source:expr $(, $stage:expr)* $(,)?) => {
+        $crate::Memo::new($crate::lazy_chain!(@compose $source $(, $stage)*))
+    };
+}
+
+/// Declares a static `ThreadsafeMemo` that runs its initializer on first use.
+///
+/// `memoize!(static CONFIG: Config = load_config());` expands to a static of
+/// type `ThreadsafeMemo<Config, fn() -> Config>`, so `CONFIG.get()` yields a
+/// `&'static Config`. The initializer is wrapped in a plain function and cannot
+/// capture anything.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! memoize {
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = $init:expr;) => {
+        $(#[$attr])*
+        $vis static $name: $crate::ThreadsafeMemo<$ty, fn() -> $ty> = $crate::ThreadsafeMemo::new({
+            fn init() -> $ty {
+                $init
+            }
+            init as fn() -> $ty
+        });
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    // loom's atomics can't be built in statics.
+    #[cfg(not(feature = "loom"))]
+    mod memoize {
+        use threadsafe_memo::ThreadsafeMemo;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::thread;
+
+        static TIMES: AtomicUsize = AtomicUsize::new(0);
+
+        fn load() -> Vec<u32> {
+            TIMES.fetch_add(1, Ordering::SeqCst);
+            vec![2, 1, 2]
+        }
+
+        memoize!(static VALUES: Vec<u32> = load(););
+        memoize!(
+            #[allow(dead_code)]
+            pub(crate) static SUM: u32 = VALUES.get().unwrap().iter().sum();
+        );
+
+        fn expansion(memo: &'static ThreadsafeMemo<u32, fn() -> u32>) -> &'static u32 {
+            memo.get().unwrap()
+        }
+
+        #[test]
+        fn once() {
+            let threads: Vec<_> = (0..8).map(|_| {
+                thread::spawn(|| {
+                    assert_eq!(*VALUES.get().unwrap(), vec![2, 1, 2]);
+                })
+            }).collect();
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            assert_eq!(*expansion(&SUM), 5);
+            assert_eq!(TIMES.load(Ordering::SeqCst), 1);
+        }
+    }
+
+    mod lazy_chain {
+        use std::cell::Cell;
+
+        #[test]
+        fn get() {
+            let loads = Cell::new(0);
+            let parses = Cell::new(0);
+            let validates = Cell::new(0);
+            {
+                let mut memo = lazy_chain!(|| {
+                    loads.set(loads.get() + 1);
+                    "212"
+                }, |x: &str| {
+                    parses.set(parses.get() + 1);
+                    x.parse::<u32>().unwrap()
+                }, |y| {
+                    validates.set(validates.get() + 1);
+                    y == 212
+                });
+                assert_eq!(loads.get(), 0);
+                assert_eq!(parses.get(), 0);
+                assert_eq!(validates.get(), 0);
+                assert!(*memo.get());
+                assert!(*memo.get());
+            }
+            assert_eq!(loads.get(), 1);
+            assert_eq!(parses.get(), 1);
+            assert_eq!(validates.get(), 1);
+        }
+
+        #[test]
+        fn source_only() {
+            let mut memo = lazy_chain!(|| 212);
+            assert_eq!(*memo.get(), 212);
+        }
+    }
+}
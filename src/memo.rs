@@ -1,6 +1,27 @@
+use core::fmt::{self, Debug};
+use core::hash::{Hash, Hasher};
+use core::ops::{Deref, DerefMut};
+use core::option;
+use core::pin::Pin;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::panic::{self, UnwindSafe};
+#[cfg(feature = "std")]
+use std::error::Error;
+
+#[derive(Clone)]
 pub struct Memo<T, F: FnOnce() -> T> {
     func: Option<F>,
     value: Option<T>,
+    poisoned: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoisonError;
+
+pub struct CalculatedMemo<T> {
+    value: T,
 }
 
 impl<T, F: FnOnce() -> T> Memo<T, F> {
@@ -8,6 +29,24 @@ impl<T, F: FnOnce() -> T> Memo<T, F> {
         Memo {
             func: Some(func),
             value: None,
+            poisoned: false,
+        }
+    }
+
+    pub fn from_parts(func: Option<F>, value: Option<T>) -> Memo<T, F> {
+        debug_assert!(func.is_none() || value.is_none(), "Memo can't have both a closure and a value!");
+        Memo {
+            func,
+            value,
+            poisoned: false,
+        }
+    }
+
+    pub fn empty() -> Memo<T, F> {
+        Memo {
+            func: None,
+            value: None,
+            poisoned: false,
         }
     }
 
@@ -15,26 +54,152 @@ impl<T, F: FnOnce() -> T> Memo<T, F> {
         Memo {
             func: None,
             value: Some(value),
+            poisoned: false,
         }
     }
 }
 
-impl<'a, T, F: FnOnce() -> T> Memo<T, F> {
+impl<T, F: FnOnce() -> T> Memo<T, F> {
     pub fn get(&mut self) -> &T {
+        match self.get_checked() {
+            Ok(value) => value,
+            Err(PoisonError) => panic!("Memo was poisoned by a panicking closure!"),
+        }
+    }
+
+    pub fn get_checked(&mut self) -> Result<&T, PoisonError> {
+        self.calculate();
+        if self.poisoned {
+            return Err(PoisonError);
+        }
+        Ok(self.value.as_ref().expect("Memo has neither a value nor a closure!"))
+    }
+
+    // The flag stays set if `func` unwinds, which works without `std`.
+    fn calculate(&mut self) {
         if let Some(func) = self.func.take() {
+            self.poisoned = true;
             self.value = Some(func());
+            self.poisoned = false;
         }
-        self.value.as_ref().unwrap()
+    }
+
+    pub fn get_or_init<G: FnOnce() -> T>(&mut self, init: G) -> &T {
+        if self.func.is_none() && self.value.is_none() {
+            self.value = Some(init());
+            self.poisoned = false;
+        }
+        self.get()
     }
 
     pub fn try_get(&self) -> Option<&T> {
         self.value.as_ref()
     }
 
+    // Leaves the memo as if it were built with `empty`, so it needs
+    // `get_or_init` or `set` before `get` will succeed.
+    pub fn take_func(&mut self) -> Option<F> {
+        self.func.take()
+    }
+
+    pub fn has_func(&self) -> bool {
+        self.func.is_some()
+    }
+
+    // The same on every memo type, for callers that don't care why a value is
+    // missing.
+    pub fn value(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+
+    // Never runs the closure, unlike `get_or`.
+    pub fn peek_or<'a>(&'a self, default: &'a T) -> &'a T {
+        self.value.as_ref().unwrap_or(default)
+    }
+
+    // Unlike `get_or_init`, an inserted value takes precedence over a pending
+    // closure, which is dropped without running.
+    pub fn get_or_insert(&mut self, value: T) -> &mut T {
+        self.get_or_insert_with(move || value)
+    }
+
+    pub fn get_or_insert_with<G: FnOnce() -> T>(&mut self, g: G) -> &mut T {
+        if self.value.is_none() {
+            let value = g();
+            self.func = None;
+            self.value = Some(value);
+            self.poisoned = false;
+        }
+        self.value.as_mut().unwrap()
+    }
+
+    // If `g` panics, the memo is left as it was.
+    pub fn recompute<G: FnOnce() -> T>(&mut self, g: G) -> &T {
+        let value = g();
+        self.func = None;
+        self.value = Some(value);
+        self.poisoned = false;
+        self.value.as_ref().unwrap()
+    }
+
+    pub fn get_or_static(&mut self, fallback: &'static T) -> &T {
+        self.calculate();
+        self.value.as_ref().unwrap_or(fallback)
+    }
+
+    pub fn is_calculated(&self) -> bool {
+        self.value.is_some()
+    }
+
+    pub fn is_uncalculated(&self) -> bool {
+        self.func.is_some()
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.calculate();
+        if self.poisoned {
+            panic!("Memo was poisoned by a panicking closure!");
+        }
+        self.value.as_mut().unwrap()
+    }
+
+    pub fn try_get_mut(&mut self) -> Option<&mut T> {
+        self.value.as_mut()
+    }
+
+    // `value` is structurally pinned and `func` is not. The closure returns an
+    // ordinary `T`, which is pinned in place once it's stored: `Memo` is only
+    // `Unpin` when `T` is, it has no `Drop` impl and these methods never move
+    // the value, so anything that could replace it, like `set` or `take`,
+    // needs an unpinned memo. Running the closure moves it, which is fine
+    // because a pinned memo never hands out `Pin<&mut F>`.
+    pub fn get_pin(self: Pin<&mut Self>) -> Pin<&T> {
+        unsafe { Pin::new_unchecked(self.get_unchecked_mut().get()) }
+    }
+
+    pub fn get_pin_mut(self: Pin<&mut Self>) -> Pin<&mut T> {
+        unsafe { Pin::new_unchecked(self.get_unchecked_mut().get_mut()) }
+    }
+
+    pub fn try_get_pin(self: Pin<&Self>) -> Option<Pin<&T>> {
+        self.get_ref().try_get().map(|value| unsafe { Pin::new_unchecked(value) })
+    }
+
+    pub fn set(&mut self, value: T) {
+        self.func = None;
+        self.value = Some(value);
+        self.poisoned = false;
+    }
+
     pub fn take(self) -> T {
         match self {
-            Memo { func: Some(func), value: None } => func(),
-            Memo { func: None, value: Some(value) } => value,
+            Memo { poisoned: true, .. } => panic!("Memo was poisoned by a panicking closure!"),
+            Memo { func: Some(func), value: None, .. } => func(),
+            Memo { func: None, value: Some(value), .. } => value,
             _ => panic!("Memo had an invalid state!")
         }
     }
@@ -42,11 +207,389 @@ impl<'a, T, F: FnOnce() -> T> Memo<T, F> {
     pub fn try_take(self) -> Option<T> {
         self.value
     }
+
+    pub fn iter(&mut self) -> option::IntoIter<&T> {
+        Some(self.get()).into_iter()
+    }
+
+    pub fn into_parts(self) -> (Option<F>, Option<T>) {
+        (self.func, self.value)
+    }
+
+    pub fn into_calculated(self) -> CalculatedMemo<T> {
+        CalculatedMemo {
+            value: self.take(),
+        }
+    }
+
+    pub fn map<U, G: FnOnce(T) -> U>(self, g: G) -> Memo<U, impl FnOnce() -> U> {
+        Memo::new(move || g(self.take()))
+    }
+
+    pub fn zip<U, G: FnOnce() -> U>(self, other: Memo<U, G>) -> Memo<(T, U), impl FnOnce() -> (T, U)> {
+        Memo::new(move || (self.take(), other.take()))
+    }
+
+    // The observer runs on the first `get` of the returned memo, even if this
+    // one was already calculated.
+    pub fn inspect<G: FnOnce(&T)>(self, g: G) -> Memo<T, impl FnOnce() -> T> {
+        Memo::new(move || {
+            let value = self.take();
+            g(&value);
+            value
+        })
+    }
+
+    pub fn compose<U, G, N>(self, next: N) -> Memo<U, impl FnOnce() -> U>
+        where G: FnOnce() -> U, N: FnOnce(T) -> Memo<U, G>
+    {
+        Memo::new(move || next(self.take()).take())
+    }
+
+    pub fn discard_if<P: FnOnce(&T) -> bool>(&mut self, pred: P, func: F) {
+        if pred(self.get()) {
+            self.func = Some(func);
+            self.value = None;
+            self.poisoned = false;
+        }
+    }
+}
+
+impl<T, F: FnOnce() -> Option<T>> Memo<Option<T>, F> {
+    pub fn flatten_option(&mut self) -> Option<&T> {
+        self.get().as_ref()
+    }
+}
+
+// Either layer may already be calculated; `take` only runs what hasn't been.
+impl<T, G: FnOnce() -> T, F: FnOnce() -> Memo<T, G>> Memo<Memo<T, G>, F> {
+    pub fn flatten(self) -> Memo<T, impl FnOnce() -> T> {
+        Memo::new(move || self.take().take())
+    }
+}
+
+impl<T: Deref, F: FnOnce() -> T> Memo<T, F> {
+    pub fn as_deref(&mut self) -> &T::Target {
+        self.get()
+    }
+
+    pub fn try_as_deref(&self) -> Option<&T::Target> {
+        self.try_get().map(Deref::deref)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T, F: FnOnce() -> T + UnwindSafe> Memo<T, F> {
+    pub fn get_or(&mut self, default: T) -> &T {
+        self.get_or_else(move || default)
+    }
+
+    // A memo that was already poisoned gets the fallback as well, just as if
+    // its closure had only now panicked.
+    pub fn get_or_else<G: FnOnce() -> T>(&mut self, fallback: G) -> &T {
+        if let Some(func) = self.func.take() {
+            self.poisoned = true;
+            self.value = Some(panic::catch_unwind(func).unwrap_or_else(|_| fallback()));
+            self.poisoned = false;
+        } else if self.poisoned {
+            self.value = Some(fallback());
+            self.poisoned = false;
+        }
+        self.value.as_ref().expect("Memo has neither a value nor a closure!")
+    }
+}
+
+impl<T, E, F: FnOnce() -> Result<T, E>> Memo<Result<T, E>, F> {
+    pub fn get_ok(&mut self) -> Result<&T, &E> {
+        self.get().as_ref()
+    }
+}
+
+impl<T> Deref for CalculatedMemo<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for CalculatedMemo<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+#[cfg(feature = "alloc")]
+pub fn try_fold_memos<T, F, B, E, G>(memos: Vec<Memo<Result<T, E>, F>>, init: B, f: G) -> Result<B, E>
+    where F: FnOnce() -> Result<T, E>, G: Fn(B, T) -> B
+{
+    let mut acc = init;
+    for memo in memos {
+        acc = f(acc, memo.take()?);
+    }
+    Ok(acc)
+}
+
+/// Builds an already-calculated memo, like `Memo::with_value`.
+///
+/// The closure type is never used, so nothing can infer it; callers must name
+/// it, and a plain `fn() -> T` pointer is the conventional choice.
+impl<T, F: FnOnce() -> T> From<T> for Memo<T, F> {
+    fn from(value: T) -> Memo<T, F> {
+        Memo::with_value(value)
+    }
+}
+
+// Yields the value once, forcing it like `take`.
+impl<T, F: FnOnce() -> T> IntoIterator for Memo<T, F> {
+    type Item = T;
+    type IntoIter = option::IntoIter<T>;
+
+    fn into_iter(self) -> option::IntoIter<T> {
+        Some(self.take()).into_iter()
+    }
+}
+
+impl<T: Debug, F: FnOnce() -> T> Debug for Memo<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_get() {
+            Some(value) => f.debug_struct("Memo")
+                            .field("state", &"calculated")
+                            .field("value", value)
+                            .finish(),
+            None if self.poisoned => f.debug_struct("Memo")
+                                      .field("state", &"poisoned")
+                                      .finish(),
+            None => f.debug_struct("Memo")
+                     .field("state", &"uncalculated")
+                     .finish(),
+        }
+    }
+}
+
+// Closures can't be compared, so every memo without a value (uncalculated,
+// empty or poisoned) equals every other one. Nothing is forced.
+impl<T: PartialEq, F: FnOnce() -> T, G: FnOnce() -> T> PartialEq<Memo<T, G>> for Memo<T, F> {
+    fn eq(&self, other: &Memo<T, G>) -> bool {
+        self.try_get() == other.try_get()
+    }
+}
+
+impl<T: Eq, F: FnOnce() -> T> Eq for Memo<T, F> {  }
+
+// Hashes `try_get`, matching `PartialEq`, so a memo's hash changes once it is
+// calculated. Don't calculate memos while they're keys in a hashed collection.
+impl<T: Hash, F: FnOnce() -> T> Hash for Memo<T, F> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.try_get().hash(state);
+    }
+}
+
+impl fmt::Display for PoisonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Memo's closure panicked while calculating its value")
+    }
 }
 
+#[cfg(feature = "std")]
+impl Error for PoisonError {  }
+
 #[cfg(test)]
 #[allow(unused_assignments)]
 mod tests {
+    mod eq {
+        use super::super::Memo;
+
+        #[test]
+        fn calculated() {
+            let mut a = Memo::new(|| 212);
+            let mut b = Memo::new(|| 212);
+            let mut c = Memo::new(|| 666);
+            a.get();
+            b.get();
+            c.get();
+            assert_eq!(a, b);
+            assert_ne!(a, c);
+        }
+
+        #[test]
+        fn mixed() {
+            let mut a = Memo::new(|| 212);
+            let b = Memo::new(|| 212);
+            a.get();
+            assert_ne!(a, b);
+            assert_ne!(b, a);
+        }
+
+        #[test]
+        fn uncalculated() {
+            let mut times = 0;
+            {
+                let a = Memo::new(|| {
+                    times += 1;
+                    212
+                });
+                let b = Memo::new(|| 666);
+                assert_eq!(a, b);
+            }
+            assert_eq!(times, 0);
+        }
+    }
+
+    mod hash {
+        use super::super::Memo;
+        use std::collections::HashSet;
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        fn hash<H: Hash>(value: &H) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        #[test]
+        fn stable() {
+            let mut memo = Memo::new(|| 212);
+            assert_eq!(hash(&memo), hash(&memo));
+            let uncalculated = hash(&memo);
+            memo.get();
+            assert_eq!(hash(&memo), hash(&memo));
+            assert_ne!(hash(&memo), uncalculated);
+            assert_eq!(hash(&memo), hash(&Memo::<u32, fn() -> u32>::with_value(212)));
+        }
+
+        #[test]
+        fn set() {
+            let mut set = HashSet::new();
+            set.insert(Memo::<u32, fn() -> u32>::with_value(212));
+            set.insert(Memo::with_value(212));
+            set.insert(Memo::with_value(666));
+            assert_eq!(set.len(), 2);
+            assert!(set.contains(&Memo::with_value(666)));
+        }
+    }
+
+    mod poison {
+        use super::super::{Memo, PoisonError};
+        use std::panic::{self, AssertUnwindSafe};
+
+        fn poisoned() -> Memo<u32, fn() -> u32> {
+            fn fail() -> u32 { panic!() }
+            let mut memo = Memo::new(fail as fn() -> u32);
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                memo.get();
+            })).unwrap_err();
+            memo
+        }
+
+        #[test]
+        fn get_checked() {
+            let mut memo = poisoned();
+            assert!(memo.is_poisoned());
+            assert!(!memo.is_calculated());
+            assert_eq!(memo.get_checked(), Err(PoisonError));
+            assert_eq!(format!("{:?}", memo), "Memo { state: \"poisoned\" }");
+        }
+
+        #[test]
+        #[should_panic(expected = "poisoned")]
+        fn get() {
+            poisoned().get();
+        }
+
+        #[test]
+        fn set() {
+            let mut memo = poisoned();
+            memo.set(212);
+            assert!(!memo.is_poisoned());
+            assert_eq!(memo.get_checked(), Ok(&212));
+        }
+
+        #[test]
+        fn get_or_init() {
+            let mut memo = poisoned();
+            assert_eq!(*memo.get_or_init(|| 212), 212);
+            assert!(!memo.is_poisoned());
+            assert_eq!(memo.get_checked(), Ok(&212));
+        }
+
+        #[test]
+        fn get_or_else() {
+            let mut memo = poisoned();
+            assert_eq!(*memo.get_or_else(|| 212), 212);
+            assert!(!memo.is_poisoned());
+            assert_eq!(*memo.get_or(200), 212);
+        }
+
+        #[test]
+        fn healthy() {
+            let mut memo = Memo::new(|| 212);
+            assert_eq!(memo.get_checked(), Ok(&212));
+            assert!(!memo.is_poisoned());
+        }
+    }
+
+    mod get_or_insert {
+        use super::super::Memo;
+
+        #[test]
+        fn uncalculated() {
+            let mut times = 0;
+            {
+                let mut memo = Memo::new(|| {
+                    times += 1;
+                    212
+                });
+                *memo.get_or_insert(666) += 1;
+                assert_eq!(*memo.get(), 667);
+            }
+            assert_eq!(times, 0);
+        }
+
+        #[test]
+        fn uncalculated_with() {
+            let mut times = 0;
+            {
+                let mut memo = Memo::new(|| {
+                    times += 1;
+                    212
+                });
+                assert_eq!(*memo.get_or_insert_with(|| 666), 666);
+                assert!(memo.is_calculated());
+                assert!(!memo.is_uncalculated());
+            }
+            assert_eq!(times, 0);
+        }
+
+        #[test]
+        fn calculated() {
+            let mut memo = Memo::new(|| 212);
+            memo.get();
+            assert_eq!(*memo.get_or_insert(666), 212);
+        }
+
+        #[test]
+        fn calculated_with() {
+            let mut inserts = 0;
+            let mut memo = Memo::new(|| 212);
+            memo.get();
+            assert_eq!(*memo.get_or_insert_with(|| {
+                inserts += 1;
+                666
+            }), 212);
+            assert_eq!(inserts, 0);
+        }
+
+        #[test]
+        fn vec() {
+            let mut memo: Memo<Vec<u32>, fn() -> Vec<u32>> = Memo::empty();
+            memo.get_or_insert_with(Vec::new).push(2);
+            memo.get_or_insert_with(Vec::new).push(1);
+            assert_eq!(*memo.get(), vec![2, 1]);
+        }
+    }
+
     mod new {
         use super::super::Memo;
 
@@ -102,6 +645,50 @@ mod tests {
             assert_eq!(times, 0);
         }
 
+        #[test]
+        fn is_calculated() {
+            let mut times = 0;
+            {
+                let mut memo = Memo::new(|| {
+                    times += 1;
+                    212
+                });
+                assert!(!memo.is_calculated());
+                assert!(memo.is_uncalculated());
+                memo.get();
+                assert!(memo.is_calculated());
+                assert!(!memo.is_uncalculated());
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        fn get_mut() {
+            let mut times = 0;
+            {
+                let mut memo = Memo::new(|| {
+                    times += 1;
+                    vec![212]
+                });
+                memo.get_mut().push(213);
+                assert_eq!(*memo.get(), vec![212, 213]);
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        fn try_get_mut() {
+            let mut times = 0;
+            {
+                let mut memo = Memo::new(|| {
+                    times += 1;
+                    212
+                });
+                assert!(memo.try_get_mut().is_none());
+            }
+            assert_eq!(times, 0);
+        }
+
         #[test]
         fn get_get() {
             let mut times = 0;
@@ -157,10 +744,883 @@ mod tests {
             }
             assert_eq!(times, 1);
         }
-    }
 
-    mod with_value {
-        use super::super::Memo;
+        #[test]
+        fn set_get() {
+            let mut times = 0;
+            {
+                let mut memo = Memo::new(|| {
+                    times += 1;
+                    200
+                });
+                memo.set(212);
+                assert_eq!(*memo.get(), 212);
+            }
+            assert_eq!(times, 0);
+        }
+
+        #[test]
+        fn get_set_get() {
+            let mut times = 0;
+            {
+                let mut memo = Memo::new(|| {
+                    times += 1;
+                    200
+                });
+                assert_eq!(*memo.get(), 200);
+                memo.set(212);
+                assert_eq!(*memo.get(), 212);
+                assert_eq!(memo.take(), 212);
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        fn get_mut_get_mut() {
+            let mut times = 0;
+            {
+                let mut memo = Memo::new(|| {
+                    times += 1;
+                    212 + times - 1
+                });
+                *memo.get_mut() += 1;
+                *memo.get_mut() += 1;
+                assert_eq!(*memo.get(), 214);
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        fn get_try_get_mut() {
+            let mut times = 0;
+            {
+                let mut memo = Memo::new(|| {
+                    times += 1;
+                    212 + times - 1
+                });
+                assert_eq!(*memo.get(), 212);
+                *memo.try_get_mut().unwrap() += 1;
+                assert_eq!(*memo.get(), 213);
+            }
+            assert_eq!(times, 1);
+        }
+    }
+
+    mod flatten_option {
+        use super::super::Memo;
+
+        #[test]
+        fn some() {
+            let mut times = 0;
+            {
+                let mut memo = Memo::new(|| {
+                    times += 1;
+                    Some(212)
+                });
+                assert_eq!(memo.flatten_option(), Some(&212));
+                assert_eq!(memo.flatten_option(), Some(&212));
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        fn none() {
+            let mut times = 0;
+            {
+                let mut memo = Memo::new(|| -> Option<u32> {
+                    times += 1;
+                    None
+                });
+                assert!(memo.flatten_option().is_none());
+                assert!(memo.flatten_option().is_none());
+            }
+            assert_eq!(times, 1);
+        }
+    }
+
+    mod get_ok {
+        use super::super::Memo;
+
+        #[test]
+        fn ok_and_err() {
+            let mut memo = Memo::new(|| -> Result<u32, &str> { Ok(212) });
+            assert_eq!(memo.get_ok(), Ok(&212));
+            let mut memo = Memo::new(|| -> Result<u32, &str> { Err("failed") });
+            assert_eq!(memo.get_ok(), Err(&"failed"));
+            assert_eq!(memo.get_ok(), Err(&"failed"));
+        }
+    }
+
+    mod zip {
+        use super::super::Memo;
+        use std::cell::Cell;
+
+        #[test]
+        fn lazy() {
+            let left = Cell::new(0);
+            let right = Cell::new(0);
+            let mut memo = Memo::new(|| {
+                left.set(left.get() + 1);
+                212
+            }).zip(Memo::new(|| {
+                right.set(right.get() + 1);
+                "memo"
+            }));
+            assert_eq!((left.get(), right.get()), (0, 0));
+            assert_eq!(*memo.get(), (212, "memo"));
+            assert_eq!(*memo.get(), (212, "memo"));
+            assert_eq!((left.get(), right.get()), (1, 1));
+        }
+
+        #[test]
+        fn calculated() {
+            let left = Cell::new(0);
+            let right = Cell::new(0);
+            let mut a = Memo::new(|| {
+                left.set(left.get() + 1);
+                212
+            });
+            a.get();
+            let b = Memo::new(|| {
+                right.set(right.get() + 1);
+                666
+            });
+            let mut memo = a.zip(b);
+            assert_eq!((left.get(), right.get()), (1, 0));
+            assert_eq!(*memo.get(), (212, 666));
+            assert_eq!((left.get(), right.get()), (1, 1));
+        }
+    }
+
+    mod inspect {
+        use super::super::Memo;
+        use std::cell::{Cell, RefCell};
+
+        #[test]
+        fn once() {
+            let seen = RefCell::new(vec![]);
+            let times = Cell::new(0);
+            let mut memo = Memo::new(|| {
+                times.set(times.get() + 1);
+                212
+            }).inspect(|&value| seen.borrow_mut().push(value));
+            assert!(seen.borrow().is_empty());
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(*seen.borrow(), [212]);
+            assert_eq!(times.get(), 1);
+        }
+
+        #[test]
+        fn with_value() {
+            let seen = Cell::new(0);
+            let memo: Memo<u32, fn() -> u32> = Memo::with_value(212);
+            let mut memo = memo.inspect(|_| seen.set(seen.get() + 1));
+            assert_eq!(seen.get(), 0);
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(seen.get(), 1);
+        }
+    }
+
+    mod flatten {
+        use super::super::Memo;
+        use std::cell::Cell;
+
+        #[test]
+        fn lazy() {
+            let outer = Cell::new(0);
+            let inner = Cell::new(0);
+            let memo = Memo::new(|| {
+                outer.set(outer.get() + 1);
+                Memo::new(|| {
+                    inner.set(inner.get() + 1);
+                    212
+                })
+            });
+            let mut flat = memo.flatten();
+            assert_eq!((outer.get(), inner.get()), (0, 0));
+            assert_eq!(*flat.get(), 212);
+            assert_eq!(*flat.get(), 212);
+            assert_eq!((outer.get(), inner.get()), (1, 1));
+        }
+
+        #[test]
+        fn calculated_outer() {
+            let outer = Cell::new(0);
+            let inner = Cell::new(0);
+            let mut memo = Memo::new(|| {
+                outer.set(outer.get() + 1);
+                Memo::new(|| {
+                    inner.set(inner.get() + 1);
+                    212
+                })
+            });
+            memo.get();
+            let mut flat = memo.flatten();
+            assert_eq!((outer.get(), inner.get()), (1, 0));
+            assert_eq!(*flat.get(), 212);
+            assert_eq!((outer.get(), inner.get()), (1, 1));
+        }
+
+        #[test]
+        fn calculated_both() {
+            let inner = Cell::new(0);
+            let mut memo = Memo::new(|| Memo::new(|| {
+                inner.set(inner.get() + 1);
+                212
+            }));
+            memo.get_mut().get();
+            let mut flat = memo.flatten();
+            assert_eq!(*flat.get(), 212);
+            assert_eq!(inner.get(), 1);
+        }
+    }
+
+    mod as_deref {
+        use super::super::Memo;
+        use std::cell::Cell;
+
+        #[test]
+        fn string() {
+            let times = Cell::new(0);
+            let mut memo = Memo::new(|| {
+                times.set(times.get() + 1);
+                String::from("memo")
+            });
+            assert_eq!(memo.try_as_deref(), None);
+            assert_eq!(memo.as_deref(), "memo");
+            assert_eq!(memo.as_deref().len(), 4);
+            assert_eq!(memo.try_as_deref(), Some("memo"));
+            assert_eq!(times.get(), 1);
+        }
+
+        #[test]
+        fn vec() {
+            let mut memo = Memo::new(|| vec![2, 1, 2]);
+            assert_eq!(memo.try_as_deref(), None);
+            assert_eq!(memo.as_deref(), &[2, 1, 2]);
+            assert_eq!(memo.try_as_deref(), Some(&[2, 1, 2][..]));
+        }
+
+        #[test]
+        #[should_panic(expected = "poisoned")]
+        fn poisoned() {
+            use std::panic::{self, AssertUnwindSafe};
+            let mut memo = Memo::new(|| -> String { panic!() });
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                memo.get();
+            })).unwrap_err();
+            memo.as_deref();
+        }
+    }
+
+    mod get_or {
+        use super::super::Memo;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[test]
+        fn get_or() {
+            let times = AtomicUsize::new(0);
+            let mut memo = Memo::new(|| {
+                times.fetch_add(1, Ordering::SeqCst);
+                212
+            });
+            assert_eq!(*memo.get_or(200), 212);
+            assert_eq!(*memo.get_or(200), 212);
+            assert_eq!(times.load(Ordering::SeqCst), 1);
+        }
+
+        #[test]
+        fn get_or_panic() {
+            let times = AtomicUsize::new(0);
+            let mut memo = Memo::new(|| -> u32 {
+                times.fetch_add(1, Ordering::SeqCst);
+                panic!();
+            });
+            assert_eq!(*memo.get_or(212), 212);
+            assert_eq!(*memo.get_or(200), 212);
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(times.load(Ordering::SeqCst), 1);
+        }
+
+        #[test]
+        fn get_or_else() {
+            let mut fallbacks = 0;
+            let mut memo = Memo::new(|| 212);
+            assert_eq!(*memo.get_or_else(|| {
+                fallbacks += 1;
+                200
+            }), 212);
+            assert_eq!(fallbacks, 0);
+        }
+
+        #[test]
+        fn get_or_else_panic() {
+            let times = AtomicUsize::new(0);
+            let mut fallbacks = 0;
+            {
+                let mut memo = Memo::new(|| -> u32 {
+                    times.fetch_add(1, Ordering::SeqCst);
+                    panic!();
+                });
+                assert_eq!(*memo.get_or_else(|| {
+                    fallbacks += 1;
+                    212
+                }), 212);
+                assert_eq!(*memo.get_or_else(|| 200), 212);
+            }
+            assert_eq!(times.load(Ordering::SeqCst), 1);
+            assert_eq!(fallbacks, 1);
+        }
+    }
+
+    mod get_or_static {
+        use super::super::Memo;
+        use std::panic::{self, AssertUnwindSafe};
+
+        static FALLBACK: u32 = 200;
+
+        #[test]
+        fn calculated() {
+            let mut memo = Memo::new(|| 212);
+            assert_eq!(*memo.get_or_static(&FALLBACK), 212);
+        }
+
+        #[test]
+        fn broken() {
+            let mut memo = Memo::new(|| -> u32 { panic!() });
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                memo.get();
+            })).unwrap_err();
+            assert_eq!(*memo.get_or_static(&FALLBACK), 200);
+        }
+    }
+
+    mod iter {
+        use super::super::Memo;
+        use std::cell::Cell;
+
+        #[test]
+        fn into_iter() {
+            let times = Cell::new(0);
+            let memo = Memo::new(|| {
+                times.set(times.get() + 1);
+                212
+            });
+            let mut iterations = 0;
+            for value in memo {
+                assert_eq!(value, 212);
+                iterations += 1;
+            }
+            assert_eq!((iterations, times.get()), (1, 1));
+        }
+
+        #[test]
+        fn iter() {
+            let times = Cell::new(0);
+            let mut memo = Memo::new(|| {
+                times.set(times.get() + 1);
+                212
+            });
+            assert_eq!(memo.iter().collect::<Vec<_>>(), [&212]);
+            assert_eq!(memo.iter().count(), 1);
+            assert_eq!(times.get(), 1);
+        }
+
+        #[test]
+        fn chain() {
+            let memo: Memo<u32, fn() -> u32> = Memo::with_value(212);
+            let values: Vec<_> = Some(200).into_iter().chain(memo).collect();
+            assert_eq!(values, [200, 212]);
+        }
+    }
+
+    mod pin {
+        use super::super::Memo;
+        use std::marker::PhantomPinned;
+        use std::pin::Pin;
+        use std::ptr;
+
+        struct SelfRef {
+            value: u32,
+            this: *const SelfRef,
+            _pinned: PhantomPinned,
+        }
+
+        impl SelfRef {
+            fn new(value: u32) -> SelfRef {
+                SelfRef {
+                    value,
+                    this: ptr::null(),
+                    _pinned: PhantomPinned,
+                }
+            }
+
+            fn init(self: Pin<&mut Self>) {
+                let this = unsafe { self.get_unchecked_mut() };
+                this.this = this;
+            }
+
+            fn is_home(&self) -> bool {
+                ptr::eq(self.this, self)
+            }
+        }
+
+        #[test]
+        fn self_referential() {
+            let mut memo = Box::pin(Memo::new(|| SelfRef::new(212)));
+            assert!(memo.as_ref().try_get_pin().is_none());
+            memo.as_mut().get_pin_mut().init();
+            assert!(memo.as_mut().get_pin().is_home());
+            assert_eq!(memo.as_mut().get_pin().value, 212);
+            let moved = memo;
+            assert!(moved.as_ref().try_get_pin().unwrap().is_home());
+        }
+
+        #[test]
+        fn unpin() {
+            let mut memo = Memo::new(|| 212);
+            assert_eq!(*Pin::new(&mut memo).get_pin(), 212);
+            memo.set(12);
+            assert_eq!(*Pin::new(&mut memo).get_pin_mut(), 12);
+        }
+    }
+
+    mod recompute {
+        use super::super::Memo;
+        use std::cell::Cell;
+        use std::panic::{self, AssertUnwindSafe};
+
+        #[test]
+        fn every_time() {
+            let gets = Cell::new(0);
+            let recomputes = Cell::new(0);
+            let mut memo = Memo::new(|| {
+                gets.set(gets.get() + 1);
+                212
+            });
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(*memo.get(), 212);
+            for i in 0..3 {
+                assert_eq!(*memo.recompute(|| {
+                    recomputes.set(recomputes.get() + 1);
+                    200 + i
+                }), 200 + i);
+                assert_eq!(*memo.get(), 200 + i);
+            }
+            assert_eq!((gets.get(), recomputes.get()), (1, 3));
+        }
+
+        #[test]
+        fn uncalculated() {
+            let times = Cell::new(0);
+            let mut memo = Memo::new(|| {
+                times.set(times.get() + 1);
+                212
+            });
+            assert_eq!(*memo.recompute(|| 200), 200);
+            assert_eq!(*memo.get(), 200);
+            assert_eq!(times.get(), 0);
+        }
+
+        #[test]
+        fn panic() {
+            let mut memo = Memo::new(|| 212);
+            memo.get();
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                memo.recompute(|| panic!());
+            })).unwrap_err();
+            assert!(!memo.is_poisoned());
+            assert_eq!(*memo.get(), 212);
+        }
+    }
+
+    mod value {
+        use super::super::Memo;
+        use std::panic::{self, AssertUnwindSafe};
+
+        #[test]
+        fn states() {
+            let mut memo = Memo::new(|| 212);
+            assert_eq!(memo.value(), None);
+            memo.get();
+            assert_eq!(memo.value(), Some(&212));
+
+            let mut memo = Memo::new(|| -> u32 { panic!() });
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                memo.get();
+            })).unwrap_err();
+            assert_eq!(memo.value(), None);
+        }
+    }
+
+    mod peek_or {
+        use super::super::Memo;
+        use std::cell::Cell;
+
+        #[test]
+        fn uncalculated() {
+            let times = Cell::new(0);
+            let mut memo = Memo::new(|| {
+                times.set(times.get() + 1);
+                212
+            });
+            assert_eq!(*memo.peek_or(&200), 200);
+            assert_eq!(*memo.peek_or(&200), 200);
+            assert_eq!(times.get(), 0);
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(*memo.peek_or(&200), 212);
+            assert_eq!(times.get(), 1);
+        }
+
+        #[test]
+        fn with_value() {
+            let memo: Memo<_, fn() -> u32> = Memo::with_value(212);
+            assert_eq!(*memo.peek_or(&200), 212);
+        }
+    }
+
+    mod empty {
+        use super::super::Memo;
+
+        #[test]
+        fn get_or_init() {
+            let mut inits = 0;
+            {
+                let mut memo: Memo<u32, fn() -> u32> = Memo::empty();
+                assert!(memo.try_get().is_none());
+                assert_eq!(*memo.get_or_init(|| {
+                    inits += 1;
+                    212
+                }), 212);
+                assert_eq!(*memo.get_or_init(|| {
+                    inits += 1;
+                    200
+                }), 212);
+                assert_eq!(*memo.get(), 212);
+            }
+            assert_eq!(inits, 1);
+        }
+
+        #[test]
+        fn new_get_or_init() {
+            let mut memo = Memo::new(|| 212);
+            assert_eq!(*memo.get_or_init(|| 200), 212);
+        }
+
+        #[test]
+        #[should_panic(expected = "Memo has neither a value nor a closure!")]
+        fn get() {
+            let mut memo: Memo<u32, fn() -> u32> = Memo::empty();
+            memo.get();
+        }
+
+        #[test]
+        fn take_func() {
+            let mut memo = Memo::new(|| 212);
+            assert!(memo.has_func());
+            let func = memo.take_func().unwrap();
+            assert!(!memo.has_func());
+            assert!(memo.take_func().is_none());
+            assert_eq!(func(), 212);
+            assert_eq!(*memo.get_or_init(|| 200), 200);
+        }
+
+        #[test]
+        #[should_panic(expected = "Memo has neither a value nor a closure!")]
+        fn take_func_get() {
+            let mut memo = Memo::new(|| 212);
+            memo.take_func();
+            memo.get();
+        }
+
+        #[test]
+        fn take_func_calculated() {
+            let mut memo = Memo::new(|| 212);
+            memo.get();
+            assert!(!memo.has_func());
+            assert!(memo.take_func().is_none());
+            assert_eq!(*memo.get(), 212);
+        }
+    }
+
+    mod discard_if {
+        use super::super::Memo;
+        use std::cell::Cell;
+
+        fn counted(times: &Cell<u32>, value: u32) -> impl FnOnce() -> u32 + '_ {
+            move || {
+                times.set(times.get() + 1);
+                value
+            }
+        }
+
+        #[test]
+        fn discard() {
+            let times = Cell::new(0);
+            let mut memo = Memo::new(counted(&times, 200));
+            memo.discard_if(|&v| v == 200, counted(&times, 212));
+            assert_eq!(times.get(), 1);
+            assert!(memo.try_get().is_none());
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(times.get(), 2);
+        }
+
+        #[test]
+        fn keep() {
+            let times = Cell::new(0);
+            let mut memo = Memo::new(counted(&times, 212));
+            memo.discard_if(|&v| v == 200, counted(&times, 200));
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(times.get(), 1);
+        }
+    }
+
+    mod try_fold_memos {
+        use super::super::{Memo, try_fold_memos};
+        use std::cell::Cell;
+
+        fn counted(times: &Cell<u32>, value: Result<u32, u32>) -> impl FnOnce() -> Result<u32, u32> + '_ {
+            move || {
+                times.set(times.get() + 1);
+                value
+            }
+        }
+
+        #[test]
+        fn ok() {
+            let times = Cell::new(0);
+            let memos = vec![
+                Memo::new(counted(&times, Ok(200))),
+                Memo::new(counted(&times, Ok(10))),
+                Memo::new(counted(&times, Ok(2))),
+            ];
+            assert_eq!(try_fold_memos(memos, 0, |acc, x| acc + x), Ok(212));
+            assert_eq!(times.get(), 3);
+        }
+
+        #[test]
+        fn short_circuit() {
+            let times = Cell::new(0);
+            let memos = vec![
+                Memo::new(counted(&times, Ok(200))),
+                Memo::new(counted(&times, Err(1))),
+                Memo::new(counted(&times, Err(2))),
+            ];
+            assert_eq!(try_fold_memos(memos, 0, |acc, x| acc + x), Err(1));
+            assert_eq!(times.get(), 2);
+        }
+    }
+
+    mod clone {
+        use super::super::Memo;
+        use std::cell::Cell;
+
+        #[test]
+        fn uncalculated() {
+            let times = Cell::new(0);
+            let memo = Memo::new(|| {
+                times.set(times.get() + 1);
+                212
+            });
+            let mut cloned = memo.clone();
+            assert_eq!(times.get(), 0);
+            assert!(cloned.try_get().is_none());
+            assert_eq!(*cloned.get(), 212);
+            assert_eq!(memo.take(), 212);
+            assert_eq!(times.get(), 2);
+        }
+
+        #[test]
+        fn calculated() {
+            let times = Cell::new(0);
+            let mut memo = Memo::new(|| {
+                times.set(times.get() + 1);
+                212
+            });
+            memo.get();
+            let cloned = memo.clone();
+            assert_eq!(*cloned.try_get().unwrap(), 212);
+            assert_eq!(times.get(), 1);
+        }
+    }
+
+    mod parts {
+        use super::super::Memo;
+
+        #[test]
+        fn uncalculated() {
+            let (func, value) = Memo::new(|| 212).into_parts();
+            assert!(value.is_none());
+            let mut memo = Memo::from_parts(func, value);
+            assert_eq!(*memo.get(), 212);
+        }
+
+        #[test]
+        fn calculated() {
+            let mut memo = Memo::new(|| 212);
+            memo.get();
+            let (func, value) = memo.into_parts();
+            assert!(func.is_none());
+            assert_eq!(value, Some(212));
+            assert_eq!(Memo::from_parts(func, value).take(), 212);
+        }
+
+        #[test]
+        fn empty() {
+            let (func, value) = Memo::<u32, fn() -> u32>::empty().into_parts();
+            assert!(func.is_none());
+            assert!(value.is_none());
+        }
+
+        #[test]
+        #[should_panic]
+        #[cfg(debug_assertions)]
+        fn both() {
+            Memo::from_parts(Some(|| 200), Some(212));
+        }
+    }
+
+    mod from {
+        use super::super::Memo;
+
+        #[test]
+        fn get() {
+            let mut memo: Memo<_, fn() -> i32> = 212.into();
+            assert!(memo.is_calculated());
+            assert_eq!(*memo.get(), 212);
+        }
+    }
+
+    mod debug {
+        use super::super::Memo;
+
+        #[test]
+        fn uncalculated() {
+            let mut times = 0;
+            {
+                let memo = Memo::new(|| {
+                    times += 1;
+                    212
+                });
+                assert_eq!(format!("{:?}", memo), "Memo { state: \"uncalculated\" }");
+            }
+            assert_eq!(times, 0);
+        }
+
+        #[test]
+        fn calculated() {
+            let mut memo = Memo::new(|| 212);
+            memo.get();
+            assert_eq!(format!("{:?}", memo), "Memo { state: \"calculated\", value: 212 }");
+        }
+    }
+
+    mod into_calculated {
+        use super::super::Memo;
+
+        #[test]
+        fn deref() {
+            let mut times = 0;
+            {
+                let calc = Memo::new(|| {
+                    times += 1;
+                    212
+                }).into_calculated();
+                assert_eq!(*calc, 212);
+                assert_eq!(*calc, 212);
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        fn deref_mut() {
+            let mut memo = Memo::new(|| vec![212]);
+            memo.get();
+            let mut calc = memo.into_calculated();
+            calc.push(213);
+            assert_eq!(calc.len(), 2);
+            assert_eq!(*calc, vec![212, 213]);
+        }
+    }
+
+    mod map {
+        use super::super::Memo;
+        use std::cell::Cell;
+
+        #[test]
+        fn get() {
+            let source = Cell::new(0);
+            let mapped = Cell::new(0);
+            {
+                let mut memo = Memo::new(|| {
+                    source.set(source.get() + 1);
+                    106
+                }).map(|x| {
+                    mapped.set(mapped.get() + 1);
+                    x * 2
+                });
+                assert_eq!(source.get(), 0);
+                assert_eq!(mapped.get(), 0);
+                assert_eq!(*memo.get(), 212);
+                assert_eq!(*memo.get(), 212);
+            }
+            assert_eq!(source.get(), 1);
+            assert_eq!(mapped.get(), 1);
+        }
+
+        #[test]
+        fn calculated() {
+            let times = Cell::new(0);
+            let mut source = Memo::new(|| {
+                times.set(times.get() + 1);
+                106
+            });
+            source.get();
+            let mut memo = source.map(|x| x * 2);
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(times.get(), 1);
+        }
+    }
+
+    mod compose {
+        use super::super::Memo;
+        use std::cell::Cell;
+
+        #[test]
+        fn get() {
+            let first = Cell::new(0);
+            let second = Cell::new(0);
+            {
+                let second = &second;
+                let mut memo = Memo::new(|| {
+                    first.set(first.get() + 1);
+                    200
+                }).compose(|x| Memo::new(move || {
+                    second.set(second.get() + 1);
+                    x + 12
+                }));
+                assert_eq!(first.get(), 0);
+                assert_eq!(second.get(), 0);
+                assert_eq!(*memo.get(), 212);
+                assert_eq!(*memo.get(), 212);
+            }
+            assert_eq!(first.get(), 1);
+            assert_eq!(second.get(), 1);
+        }
+
+        #[test]
+        fn with_value() {
+            let memo: Memo<u32, fn() -> u32> = Memo::with_value(200);
+            let mut memo = memo.compose(|x| Memo::<u32, fn() -> u32>::with_value(x + 12));
+            assert!(memo.try_get().is_none());
+            assert_eq!(*memo.get(), 212);
+        }
+    }
+
+    mod with_value {
+        use super::super::Memo;
 
         #[test]
         fn get() {
@@ -189,5 +1649,27 @@ mod tests {
             memo = Memo::with_value(212);
             assert_eq!(memo.try_take().unwrap(), 212);
         }
+
+        #[test]
+        fn is_calculated() {
+            let mut memo = Memo::new(|| { 200 });
+            memo = Memo::with_value(212);
+            assert!(memo.is_calculated());
+            assert!(!memo.is_uncalculated());
+        }
+
+        #[test]
+        fn get_mut() {
+            let mut memo = Memo::new(|| { 200 });
+            memo = Memo::with_value(212);
+            assert_eq!(*memo.get_mut(), 212);
+        }
+
+        #[test]
+        fn try_get_mut() {
+            let mut memo = Memo::new(|| { 200 });
+            memo = Memo::with_value(212);
+            assert_eq!(*memo.try_get_mut().unwrap(), 212);
+        }
     }
 }
@@ -0,0 +1,75 @@
+use alloc::boxed::Box;
+use memo::Memo;
+
+pub struct MemoCell<T> {
+    memo: Memo<T, Box<dyn FnOnce() -> T>>,
+}
+
+impl<T> MemoCell<T> {
+    pub fn new<F: FnOnce() -> T + 'static>(func: F) -> MemoCell<T> {
+        MemoCell {
+            memo: Memo::new(Box::new(func)),
+        }
+    }
+
+    pub fn with_value(value: T) -> MemoCell<T> {
+        MemoCell {
+            memo: Memo::with_value(value),
+        }
+    }
+}
+
+impl<T> MemoCell<T> {
+    pub fn get(&mut self) -> &T {
+        self.memo.get()
+    }
+
+    pub fn try_get(&self) -> Option<&T> {
+        self.memo.try_get()
+    }
+
+    pub fn take(self) -> T {
+        self.memo.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemoCell;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn heterogeneous() {
+        let times = Rc::new(Cell::new(0));
+        let offset = 200;
+        let mut memos = vec![
+            MemoCell::new(|| 212),
+            MemoCell::new(move || offset + 12),
+            MemoCell::with_value(666),
+        ];
+        memos.push({
+            let times = times.clone();
+            MemoCell::new(move || {
+                times.set(times.get() + 1);
+                -212
+            })
+        });
+        assert!(memos.iter().all(|memo| memo.try_get().is_none() || memo.try_get() == Some(&666)));
+        let values: Vec<i32> = memos.iter_mut().map(|memo| *memo.get()).collect();
+        assert_eq!(values, [212, 212, 666, -212]);
+        assert_eq!(memos[3].try_get(), Some(&-212));
+        assert_eq!(*memos[3].get(), -212);
+        assert_eq!(times.get(), 1);
+    }
+
+    #[test]
+    fn take() {
+        let memo = MemoCell::new(|| String::from("memo"));
+        assert_eq!(memo.take(), "memo");
+        let mut memo = MemoCell::new(|| String::from("memo"));
+        assert_eq!(memo.try_get(), None);
+        memo.get();
+        assert_eq!(memo.take(), "memo");
+    }
+}
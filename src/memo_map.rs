@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+pub struct MemoMap<K: Eq + Hash, V> {
+    values: HashMap<K, V>,
+}
+
+impl<K: Eq + Hash, V> MemoMap<K, V> {
+    pub fn new() -> MemoMap<K, V> {
+        MemoMap {
+            values: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V> Default for MemoMap<K, V> {
+    fn default() -> MemoMap<K, V> {
+        MemoMap::new()
+    }
+}
+
+impl<K: Eq + Hash, V> MemoMap<K, V> {
+    pub fn get_or_compute<F: FnOnce() -> V>(&mut self, key: K, f: F) -> &V {
+        self.values.entry(key).or_insert_with(f)
+    }
+
+    pub fn try_get(&self, key: &K) -> Option<&V> {
+        self.values.get(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemoMap;
+
+    #[test]
+    fn get_or_compute() {
+        let mut times = [0; 2];
+        let mut memo = MemoMap::new();
+        assert_eq!(*memo.get_or_compute(0, || { times[0] += 1; 212 }), 212);
+        assert_eq!(*memo.get_or_compute(1, || { times[1] += 1; 666 }), 666);
+        assert_eq!(*memo.get_or_compute(0, || { times[0] += 1; 0 }), 212);
+        assert_eq!(*memo.get_or_compute(1, || { times[1] += 1; 0 }), 666);
+        assert_eq!(times, [1, 1]);
+    }
+
+    #[test]
+    fn try_get() {
+        let mut memo = MemoMap::new();
+        assert_eq!(memo.try_get(&"a"), None);
+        memo.get_or_compute("a", || 212);
+        assert_eq!(memo.try_get(&"a"), Some(&212));
+        assert_eq!(memo.try_get(&"b"), None);
+    }
+}
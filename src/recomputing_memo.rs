@@ -0,0 +1,67 @@
+pub struct RecomputingMemo<T, F: FnMut() -> T> {
+    func: F,
+    value: Option<T>,
+}
+
+impl<T, F: FnMut() -> T> RecomputingMemo<T, F> {
+    pub fn new(func: F) -> RecomputingMemo<T, F> {
+        RecomputingMemo {
+            func,
+            value: None,
+        }
+    }
+}
+
+impl<T, F: FnMut() -> T> RecomputingMemo<T, F> {
+    pub fn get(&mut self) -> &T {
+        if self.value.is_none() {
+            self.value = Some((self.func)());
+        }
+        self.value.as_ref().unwrap()
+    }
+
+    pub fn try_get(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+
+    pub fn invalidate(&mut self) {
+        self.value = None;
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_assignments)]
+mod tests {
+    use super::RecomputingMemo;
+
+    #[test]
+    fn get_get() {
+        let mut times = 0;
+        {
+            let mut memo = RecomputingMemo::new(|| {
+                times += 1;
+                212 + times - 1
+            });
+            assert!(memo.try_get().is_none());
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(*memo.get(), 212);
+        }
+        assert_eq!(times, 1);
+    }
+
+    #[test]
+    fn get_invalidate_get() {
+        let mut times = 0;
+        {
+            let mut memo = RecomputingMemo::new(|| {
+                times += 1;
+                212 + times - 1
+            });
+            assert_eq!(*memo.get(), 212);
+            memo.invalidate();
+            assert!(memo.try_get().is_none());
+            assert_eq!(*memo.get(), 213);
+        }
+        assert_eq!(times, 2);
+    }
+}
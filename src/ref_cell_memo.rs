@@ -0,0 +1,151 @@
+use core::cell::{Ref, RefCell};
+use memo::Memo;
+
+// A safe alternative to `AliasableMemo` for memos behind shared pointers. A
+// closure that reenters its own memo panics with `RefCell`'s borrow error
+// instead, and outstanding `Ref`s only block calculating, not reading.
+pub struct RefCellMemo<T, F: FnOnce() -> T> {
+    memo: RefCell<Memo<T, F>>,
+}
+
+impl<T, F: FnOnce() -> T> RefCellMemo<T, F> {
+    pub fn new(func: F) -> RefCellMemo<T, F> {
+        RefCellMemo {
+            memo: RefCell::new(Memo::new(func)),
+        }
+    }
+
+    pub fn with_value(value: T) -> RefCellMemo<T, F> {
+        RefCellMemo {
+            memo: RefCell::new(Memo::with_value(value)),
+        }
+    }
+}
+
+impl<T, F: FnOnce() -> T> RefCellMemo<T, F> {
+    // Panics if the closure panics or has already panicked, like `Memo::get`.
+    pub fn get<'a>(&'a self) -> Ref<'a, T> {
+        if !self.memo.borrow().is_calculated() {
+            self.memo.borrow_mut().get();
+        }
+        Ref::map(self.memo.borrow(), |memo| memo.try_get().unwrap())
+    }
+
+    pub fn try_get<'a>(&'a self) -> Option<Ref<'a, T>> {
+        Ref::filter_map(self.memo.borrow(), Memo::try_get).ok()
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.memo.borrow().is_poisoned()
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.memo.get_mut().get_mut()
+    }
+
+    pub fn take(self) -> T {
+        self.memo.into_inner().take()
+    }
+
+    pub fn into_memo(self) -> Memo<T, F> {
+        self.memo.into_inner()
+    }
+}
+
+impl<T, F: FnOnce() -> T> From<Memo<T, F>> for RefCellMemo<T, F> {
+    fn from(memo: Memo<T, F>) -> RefCellMemo<T, F> {
+        RefCellMemo {
+            memo: RefCell::new(memo),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod get {
+        use super::super::RefCellMemo;
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        #[test]
+        fn shared() {
+            let times = Rc::new(Cell::new(0));
+            let memo = Rc::new(RefCellMemo::new({
+                let times = times.clone();
+                move || {
+                    times.set(times.get() + 1);
+                    212
+                }
+            }));
+            let other = memo.clone();
+            assert!(memo.try_get().is_none());
+            assert_eq!(*other.get(), 212);
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(memo.try_get().map(|value| *value), Some(212));
+            assert_eq!(times.get(), 1);
+        }
+
+        #[test]
+        fn outstanding_refs() {
+            let memo = RefCellMemo::new(|| 212);
+            let first = memo.get();
+            let second = memo.get();
+            assert_eq!((*first, *second), (212, 212));
+        }
+
+        #[test]
+        fn with_value() {
+            let mut memo: RefCellMemo<u32, fn() -> u32> = RefCellMemo::with_value(212);
+            assert_eq!(*memo.get(), 212);
+            *memo.get_mut() += 1;
+            assert_eq!(memo.take(), 213);
+        }
+    }
+
+    mod reentrancy {
+        use super::super::RefCellMemo;
+        use std::cell::Cell;
+        use std::panic::{self, AssertUnwindSafe};
+
+        type Probe = RefCellMemo<u32, Box<dyn FnOnce() -> u32>>;
+
+        // A memo whose closure reads the memo itself.
+        fn reentrant() -> &'static Probe {
+            let slot: &'static Cell<Option<&Probe>> = Box::leak(Box::new(Cell::new(None)));
+            let probe: Probe = RefCellMemo::new(Box::new(move || *slot.get().unwrap().get() + 1));
+            let memo = Box::leak(Box::new(probe));
+            slot.set(Some(memo));
+            memo
+        }
+
+        #[test]
+        #[should_panic(expected = "already mutably borrowed")]
+        fn borrow() {
+            reentrant().get();
+        }
+
+        #[test]
+        fn poisons() {
+            let memo = reentrant();
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                memo.get();
+            })).unwrap_err();
+            assert!(memo.is_poisoned());
+            assert!(memo.try_get().is_none());
+        }
+    }
+
+    mod from {
+        use super::super::RefCellMemo;
+        use memo::Memo;
+
+        #[test]
+        fn round_trip() {
+            let mut memo = Memo::new(|| 212);
+            memo.get();
+            let memo = RefCellMemo::from(memo);
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(memo.into_memo().take(), 212);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use memo::Memo;
+
+// `Memo` consumes its closure, so resetting needs a spare copy of it; keeping
+// the spare here means only memos built to be reset pay for it.
+pub struct ResettableMemo<T, F: FnOnce() -> T + Clone> {
+    memo: Memo<T, F>,
+    spare: F,
+}
+
+impl<T, F: FnOnce() -> T + Clone> Memo<T, F> {
+    pub fn new_resettable(func: F) -> ResettableMemo<T, F> {
+        ResettableMemo {
+            memo: Memo::new(func.clone()),
+            spare: func,
+        }
+    }
+}
+
+impl<T, F: FnOnce() -> T + Clone> ResettableMemo<T, F> {
+    pub fn get(&mut self) -> &T {
+        self.memo.get()
+    }
+
+    pub fn try_get(&self) -> Option<&T> {
+        self.memo.try_get()
+    }
+
+    pub fn is_calculated(&self) -> bool {
+        self.memo.is_calculated()
+    }
+
+    pub fn reset(&mut self) {
+        self.memo = Memo::new(self.spare.clone());
+    }
+
+    pub fn into_memo(self) -> Memo<T, F> {
+        self.memo
+    }
+}
+
+// Every attempt needs its own copy of the closure, so retrying takes
+// `F: Clone` like `new_resettable` rather than `FnMut`, which `Memo` can't
+// store.
+impl<T, E, F: FnOnce() -> Result<T, E> + Clone> Memo<Result<T, E>, F> {
+    pub fn new_retrying(func: F) -> ResettableMemo<Result<T, E>, F> {
+        Memo::new_resettable(func)
+    }
+}
+
+impl<T, E, F: FnOnce() -> Result<T, E> + Clone> ResettableMemo<Result<T, E>, F> {
+    pub fn get_ok(&mut self) -> Result<&T, &E> {
+        self.memo.get_ok()
+    }
+
+    // Reruns the closure if the last attempt failed; a success is kept.
+    pub fn get_ok_retrying(&mut self) -> Result<&T, &E> {
+        if let Some(Err(_)) = self.memo.try_get() {
+            self.reset();
+        }
+        self.get_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod resettable {
+        use memo::Memo;
+        use std::cell::Cell;
+
+        #[test]
+        fn get_reset_get() {
+            let times = Cell::new(0);
+            let mut memo = Memo::new_resettable(|| {
+                times.set(times.get() + 1);
+                212 + times.get() - 1
+            });
+            assert_eq!(*memo.get(), 212);
+            memo.reset();
+            assert!(memo.try_get().is_none());
+            assert_eq!(*memo.get(), 213);
+            assert_eq!(*memo.get(), 213);
+            assert_eq!(times.get(), 2);
+        }
+
+        #[test]
+        fn reset() {
+            let times = Cell::new(0);
+            let mut memo = Memo::new_resettable(|| {
+                times.set(times.get() + 1);
+                212
+            });
+            memo.reset();
+            assert_eq!(*memo.get(), 212);
+            assert_eq!(times.get(), 1);
+        }
+
+        #[test]
+        fn into_memo() {
+            let mut memo = Memo::new_resettable(|| 212);
+            memo.get();
+            assert!(memo.is_calculated());
+            assert_eq!(memo.into_memo().try_get(), Some(&212));
+        }
+    }
+
+    mod retrying {
+        use memo::Memo;
+        use std::cell::Cell;
+
+        #[test]
+        fn success_first() {
+            let times = Cell::new(0);
+            let mut memo = Memo::new_retrying(|| -> Result<u32, &str> {
+                times.set(times.get() + 1);
+                Ok(212)
+            });
+            assert_eq!(memo.get_ok_retrying(), Ok(&212));
+            assert_eq!(memo.get_ok_retrying(), Ok(&212));
+            assert_eq!(times.get(), 1);
+        }
+
+        #[test]
+        fn failure_then_success() {
+            let times = Cell::new(0);
+            let mut memo = Memo::new_retrying(|| {
+                times.set(times.get() + 1);
+                match times.get() {
+                    1 | 2 => Err(times.get()),
+                    _ => Ok(212),
+                }
+            });
+            assert_eq!(memo.get_ok_retrying(), Err(&1));
+            assert_eq!(memo.get_ok(), Err(&1));
+            assert_eq!(memo.get_ok_retrying(), Err(&2));
+            assert_eq!(memo.get_ok_retrying(), Ok(&212));
+            assert_eq!(memo.get_ok_retrying(), Ok(&212));
+            assert_eq!(times.get(), 3);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::Error as DeError;
+#[cfg(feature = "std")]
+use serde::ser::Error as SerError;
+use memo::Memo;
+use aliasable_memo::AliasableMemo;
+#[cfg(feature = "std")]
+use threadsafe_memo::ThreadsafeMemo;
+#[cfg(feature = "std")]
+use blocker::Blocker;
+
+// Memos serialize as a `Memo` enum without forcing, so an uncalculated memo
+// is written as `Uncalculated` and a calculated one as `Calculated(value)`,
+// which stays unambiguous when the value is itself an `Option`. Only
+// calculated memos can be read back, since there is no closure to restore;
+// poisoned `ThreadsafeMemo`s refuse to serialize at all.
+#[derive(Serialize)]
+#[serde(rename = "Memo")]
+enum MemoRef<'a, T: 'a> {
+    Uncalculated,
+    Calculated(&'a T),
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Memo")]
+enum MemoValue<T> {
+    Uncalculated,
+    Calculated(T),
+}
+
+impl<'a, T> From<Option<&'a T>> for MemoRef<'a, T> {
+    fn from(value: Option<&'a T>) -> MemoRef<'a, T> {
+        match value {
+            Some(value) => MemoRef::Calculated(value),
+            None => MemoRef::Uncalculated,
+        }
+    }
+}
+
+impl<T: Serialize, F: FnOnce() -> T> Serialize for Memo<T, F> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MemoRef::from(self.try_get()).serialize(serializer)
+    }
+}
+
+impl<T: Serialize, F: FnOnce() -> T> Serialize for AliasableMemo<T, F> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MemoRef::from(self.try_get()).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Serialize, F: FnOnce() -> T, B: Blocker> Serialize for ThreadsafeMemo<T, F, B> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.try_get() {
+            Ok(value) => MemoRef::from(value).serialize(serializer),
+            Err(_) => Err(S::Error::custom("cannot serialize a poisoned ThreadsafeMemo")),
+        }
+    }
+}
+
+fn deserialize_value<'de, T: Deserialize<'de>, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+    match MemoValue::deserialize(deserializer)? {
+        MemoValue::Calculated(value) => Ok(value),
+        MemoValue::Uncalculated => Err(D::Error::custom("cannot deserialize an uncalculated memo")),
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Memo<T, fn() -> T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_value(deserializer).map(Memo::with_value)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for AliasableMemo<T, fn() -> T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_value(deserializer).map(AliasableMemo::with_value)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for ThreadsafeMemo<T, fn() -> T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_value(deserializer).map(ThreadsafeMemo::with_value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod serde {
+        use memo::Memo;
+        use aliasable_memo::AliasableMemo;
+        use threadsafe_memo::ThreadsafeMemo;
+        use serde_json;
+        use std::panic;
+
+        #[test]
+        fn memo_round_trip() {
+            let mut memo: Memo<i32, fn() -> i32> = Memo::new(|| 212);
+            memo.get();
+            let json = serde_json::to_string(&memo).unwrap();
+            assert_eq!(json, r#"{"Calculated":212}"#);
+            let memo: Memo<i32, fn() -> i32> = serde_json::from_str(&json).unwrap();
+            assert_eq!(memo.try_get(), Some(&212));
+        }
+
+        #[test]
+        fn uncalculated() {
+            let memo: Memo<i32, fn() -> i32> = Memo::new(|| 212);
+            let json = serde_json::to_string(&memo).unwrap();
+            assert_eq!(json, r#""Uncalculated""#);
+            assert!(serde_json::from_str::<Memo<i32, fn() -> i32>>(&json).is_err());
+        }
+
+        #[test]
+        fn option_round_trip() {
+            for value in [None, Some(212)] {
+                let memo: Memo<Option<i32>, fn() -> Option<i32>> = Memo::with_value(value);
+                let json = serde_json::to_string(&memo).unwrap();
+                let memo: Memo<Option<i32>, fn() -> Option<i32>> = serde_json::from_str(&json).unwrap();
+                assert_eq!(memo.try_get(), Some(&value));
+            }
+            let memo: ThreadsafeMemo<Option<i32>, fn() -> Option<i32>> = ThreadsafeMemo::with_value(None);
+            let json = serde_json::to_string(&memo).unwrap();
+            assert_eq!(json, r#"{"Calculated":null}"#);
+            let memo: ThreadsafeMemo<Option<i32>, fn() -> Option<i32>> = serde_json::from_str(&json).unwrap();
+            assert_eq!(memo.try_get(), Ok(Some(&None)));
+        }
+
+        #[test]
+        fn aliasable_memo_round_trip() {
+            let memo: AliasableMemo<i32, fn() -> i32> = AliasableMemo::new(|| 212);
+            memo.get();
+            let json = serde_json::to_string(&memo).unwrap();
+            let memo: AliasableMemo<i32, fn() -> i32> = serde_json::from_str(&json).unwrap();
+            assert_eq!(memo.try_get(), Some(&212));
+        }
+
+        #[test]
+        fn threadsafe_memo_round_trip() {
+            let memo: ThreadsafeMemo<i32, fn() -> i32> = ThreadsafeMemo::new(|| 212);
+            memo.get().unwrap();
+            let json = serde_json::to_string(&memo).unwrap();
+            let memo: ThreadsafeMemo<i32, fn() -> i32> = serde_json::from_str(&json).unwrap();
+            assert_eq!(memo.try_get(), Ok(Some(&212)));
+        }
+
+        #[test]
+        #[allow(unused_must_use)]
+        fn threadsafe_memo_poisoned() {
+            let memo = ThreadsafeMemo::new(|| -> i32 { panic!() });
+            panic::catch_unwind(|| {
+                memo.get();
+            }).unwrap_err();
+            assert!(serde_json::to_string(&memo).is_err());
+        }
+    }
+}
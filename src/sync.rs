@@ -0,0 +1,19 @@
+// `ThreadsafeMemo` and `ParkBlocker` take their atomics, lock and threads from
+// here so that the `loom` feature can swap in loom's models, which explore
+// every interleaving of the state machine. Only what loom can model goes
+// through the shim; scoped threads and the like still come from `std`, and
+// the memo's `UnsafeCell` isn't tracked, since loom's cell can't hand out the
+// long-lived references the memo does.
+#[cfg(not(feature = "loom"))]
+pub use std::sync::atomic::{self, AtomicUsize, AtomicBool, AtomicPtr, Ordering};
+#[cfg(not(feature = "loom"))]
+pub use std::sync::{Mutex, MutexGuard};
+#[cfg(not(feature = "loom"))]
+pub use std::{hint, thread};
+
+#[cfg(feature = "loom")]
+pub use loom::sync::atomic::{self, AtomicUsize, AtomicBool, AtomicPtr, Ordering};
+#[cfg(feature = "loom")]
+pub use loom::sync::{Mutex, MutexGuard};
+#[cfg(feature = "loom")]
+pub use loom::{hint, thread};
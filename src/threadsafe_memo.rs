@@ -1,9 +1,17 @@
 use std::cell::UnsafeCell;
-use std::sync::atomic::{AtomicUsize, AtomicBool, Ordering};
+use std::convert::TryFrom;
+use std::sync::Arc;
+use std::mem;
 use std::ptr;
-use std::thread::{self, Thread};
-use std::marker::Sync;
-use std::panic::{UnwindSafe, RefUnwindSafe};
+use std::cmp::Reverse;
+use std::time::{Duration, Instant};
+use std::process;
+use std::marker::{PhantomData, Sync};
+use std::panic::{self, AssertUnwindSafe, UnwindSafe, RefUnwindSafe};
+use blocker::{Blocker, ParkBlocker};
+use sync::{atomic, hint, thread, AtomicUsize, AtomicBool, AtomicPtr, Ordering, Mutex, MutexGuard};
+use memo::{Memo, PoisonError};
+use aliasable_memo::AliasableMemo;
 
 const UNCALCULATED: usize = 1;
 const WORKING: usize = 0; // either calculating or unpoisoning
@@ -11,15 +19,89 @@ const CALCULATED: usize = 2;
 const POISONED: usize = 3;
 const STATE_MASK: usize = 3;
 
-struct SpinState {
-    thread: Option<Thread>,
-    signaled: AtomicBool,
-    next: *mut SpinState,
+const QUEUED: usize = 0;
+const CLAIMED: usize = 1; // being woken by the finishing thread
+const SIGNALED: usize = 2;
+const ABANDONED: usize = 3; // timed out; freed by the finishing thread
+
+// While working, the state holds the address of the newest `SpinState` with
+// `WORKING` in its low two bits. That is only unambiguous if those bits of the
+// address are always clear, which the alignment guarantees no matter what the
+// blocker is or where the node lives, even on targets where `usize` itself is
+// less aligned. `WORKING` is zero, so an empty queue is just the bare state.
+#[repr(align(4))]
+struct SpinState<B> {
+    blocker: Option<B>,
+    priority: u8,
+    status: AtomicUsize,
+    next: *mut SpinState<B>,
 }
 
-struct Finish<'a> {
+const _: () = assert!(mem::align_of::<SpinState<()>>() > STATE_MASK);
+
+struct Finish<'a, T: 'a, F: FnOnce() -> T + 'a, B: Blocker + 'a> {
     destination_state: usize,
-    state: &'a AtomicUsize,
+    memo: &'a ThreadsafeMemo<T, F, B>,
+}
+
+type Callback<T> = Box<dyn FnOnce(Result<&T, PoisonError>) + Send>;
+
+// Allocated by the first `on_complete`, so memos that never register a
+// callback only pay for a null pointer.
+struct Callbacks<T> {
+    list: AtomicPtr<Mutex<Vec<Callback<T>>>>,
+    owned: PhantomData<Box<Mutex<Vec<Callback<T>>>>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryError<E> {
+    Failed(E),
+    Poisoned,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryGetResult<T> {
+    Computed(T),
+    InProgress,
+    Poisoned,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoState {
+    Uncalculated,
+    Working,
+    Calculated,
+    Poisoned,
+}
+
+// What `wait` and `wait_timeout` found once they stopped waiting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WaitResult {
+    Ready,
+    // Nobody was calculating, so there was nothing to wait for.
+    Uncalculated,
+    // The deadline passed while another thread was still calculating.
+    TimedOut,
+}
+
+#[derive(Clone, Copy)]
+struct PoisonHook {
+    log: fn(),
+    abort: fn(),
+}
+
+// Aborts whenever it is dropped, so the calculating paths forget it once the
+// closure returns and only an unwind out of the closure drops it.
+// `thread::panicking` can't tell that apart from a calculation that a
+// destructor runs while some other panic unwinds.
+struct AbortOnUnwind {
+    hook: Option<PoisonHook>,
+}
+
+// Dropping the guard without a value hands the memo back uncalculated.
+pub struct ComputeGuard<'a, T: 'a, F: FnOnce() -> T + 'a, B: Blocker + 'a = ParkBlocker> {
+    memo: &'a ThreadsafeMemo<T, F, B>,
+    finish: Finish<'a, T, F, B>,
 }
 
 struct ThreadsafeMemoCore<T, F: FnOnce() -> T> {
@@ -27,50 +109,235 @@ struct ThreadsafeMemoCore<T, F: FnOnce() -> T> {
     value: Option<T>,
 }
 
-pub struct ThreadsafeMemo<T, F: FnOnce() -> T> {
+pub struct ThreadsafeMemo<T, F: FnOnce() -> T, B: Blocker = ParkBlocker> {
     state: AtomicUsize,
+    warming: AtomicBool,
+    on_poison: Option<PoisonHook>,
+    spin_limit: usize,
+    callbacks: Callbacks<T>,
+    error_readers: Mutex<()>,
     core: UnsafeCell<ThreadsafeMemoCore<T, F>>,
+    blocker: PhantomData<B>,
 }
 
 impl<T, F: FnOnce() -> T> ThreadsafeMemo<T, F> {
-    pub fn new(func: F) -> ThreadsafeMemo<T, F> {
+    // `const` so that `memoize!` can build statics.
+    #[cfg(not(feature = "loom"))]
+    pub const fn new(func: F) -> ThreadsafeMemo<T, F> {
         ThreadsafeMemo {
             state: AtomicUsize::new(UNCALCULATED),
+            warming: AtomicBool::new(false),
+            on_poison: None,
+            spin_limit: 0,
+            callbacks: Callbacks::new(),
+            error_readers: Mutex::new(()),
             core: UnsafeCell::new(ThreadsafeMemoCore {
                 func: Some(func),
                 value: None,
             }),
+            blocker: PhantomData,
         }
     }
 
+    // loom's atomics can't be built in a `const fn`.
+    #[cfg(feature = "loom")]
+    pub fn new(func: F) -> ThreadsafeMemo<T, F> {
+        ThreadsafeMemo::with_blocker(func)
+    }
+
     pub fn with_value(value: T) -> ThreadsafeMemo<T, F> {
         ThreadsafeMemo {
             state: AtomicUsize::new(CALCULATED),
+            warming: AtomicBool::new(false),
+            on_poison: None,
+            spin_limit: 0,
+            callbacks: Callbacks::new(),
+            error_readers: Mutex::new(()),
             core: UnsafeCell::new(ThreadsafeMemoCore {
                 func: None,
                 value: Some(value),
             }),
+            blocker: PhantomData,
+        }
+    }
+
+    pub(crate) fn empty() -> ThreadsafeMemo<T, F> {
+        ThreadsafeMemo {
+            state: AtomicUsize::new(UNCALCULATED),
+            warming: AtomicBool::new(false),
+            on_poison: None,
+            spin_limit: 0,
+            callbacks: Callbacks::new(),
+            error_readers: Mutex::new(()),
+            core: UnsafeCell::new(ThreadsafeMemoCore {
+                func: None,
+                value: None,
+            }),
+            blocker: PhantomData,
+        }
+    }
+
+    pub fn new_eager_if(func: F, eager: bool) -> ThreadsafeMemo<T, F> {
+        let memo = ThreadsafeMemo::new(func);
+        if eager {
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| memo.get().is_ok()));
+        }
+        memo
+    }
+
+    // Waiters spin up to `limit` times before parking, which is cheaper when
+    // calculations are short.
+    pub fn with_spin_limit(func: F, limit: usize) -> ThreadsafeMemo<T, F> {
+        let mut memo = ThreadsafeMemo::new(func);
+        memo.spin_limit = limit;
+        memo
+    }
+
+    pub fn new_arc(func: F) -> Arc<ThreadsafeMemo<T, F>> {
+        Arc::new(ThreadsafeMemo::new(func))
+    }
+
+    pub fn new_abort_on_poison(func: F, log: fn()) -> ThreadsafeMemo<T, F> {
+        let mut memo = ThreadsafeMemo::new(func);
+        memo.on_poison = Some(PoisonHook {
+            log,
+            abort,
+        });
+        memo
+    }
+}
+
+impl<T, F: FnOnce() -> T, B: Blocker> ThreadsafeMemo<T, F, B> {
+    pub fn with_blocker(func: F) -> ThreadsafeMemo<T, F, B> {
+        ThreadsafeMemo {
+            state: AtomicUsize::new(UNCALCULATED),
+            warming: AtomicBool::new(false),
+            on_poison: None,
+            spin_limit: 0,
+            callbacks: Callbacks::new(),
+            error_readers: Mutex::new(()),
+            core: UnsafeCell::new(ThreadsafeMemoCore {
+                func: Some(func),
+                value: None,
+            }),
+            blocker: PhantomData,
         }
     }
 }
 
-#[cfg(feature = "unstable")]
-fn atomic_usize_into_inner(atomic: AtomicUsize) -> usize {
-    atomic.into_inner()
+// the waiter queue is packed into the high bits while working
+fn decode_state(state: usize) -> MemoState {
+    match state & STATE_MASK {
+        UNCALCULATED => MemoState::Uncalculated,
+        CALCULATED => MemoState::Calculated,
+        POISONED => MemoState::Poisoned,
+        _ => MemoState::Working,
+    }
 }
 
-#[cfg(not(feature = "unstable"))]
-fn atomic_usize_into_inner(atomic: AtomicUsize) -> usize {
-    atomic.load(Ordering::Acquire)
+fn abort() {
+    process::abort();
 }
 
-impl<'a, T, F: FnOnce() -> T> ThreadsafeMemo<T, F> {
-    pub fn get(&self) -> Result<&T, ()> {
+impl<T, F: FnOnce() -> T, B: Blocker> ThreadsafeMemo<T, F, B> {
+    pub fn get(&self) -> Result<&T, PoisonError> {
+        self.get_with_priority(0)
+    }
+
+    pub fn expect(&self, msg: &str) -> &T {
+        match self.get() {
+            Ok(value) => value,
+            Err(err) => panic!("{}: {}", msg, err),
+        }
+    }
+
+    pub fn get_cloned(&self) -> Result<T, PoisonError> where T: Clone {
+        self.get().cloned()
+    }
+
+    pub fn into_arc(self) -> Arc<ThreadsafeMemo<T, F, B>> {
+        Arc::new(self)
+    }
+
+    pub fn force(&self) -> Result<(), PoisonError> {
+        self.get().map(|_| ())
+    }
+
+    pub fn get_with_priority(&self, priority: u8) -> Result<&T, PoisonError> {
+        self.get_with(priority, |core| core.value = Some(core.func.take().unwrap()()))
+    }
+
+    fn get_with<C>(&self, priority: u8, calculate: C) -> Result<&T, PoisonError>
+        where C: FnOnce(&mut ThreadsafeMemoCore<T, F>)
+    {
+        self.try_calculate_with(priority, |core| -> Result<(), ()> {
+            calculate(core);
+            Ok(())
+        }).map_err(|_| PoisonError)
+    }
+
+    pub(crate) fn try_get_or_init<E, G>(&self, init: G) -> Result<&T, TryError<E>>
+        where G: FnOnce() -> Result<T, E>
+    {
+        self.try_calculate_with(0, |core| {
+            core.value = Some(init()?);
+            Ok(())
+        })
+    }
+
+    // Like `OnceLock::set`, this waits out another thread's calculation and
+    // only fails once the memo is calculated or poisoned, so calling it from
+    // inside the memo's own closure deadlocks. The construction-time closure
+    // is dropped unused.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            match state {
+                CALCULATED | POISONED => return Err(value),
+                UNCALCULATED => {
+                    if let Some(guard) = self.try_begin_compute() {
+                        guard.set(value);
+                        return Ok(());
+                    }
+                    state = self.state.load(Ordering::Acquire);
+                },
+                _ => state = self.block(0, state, None).unwrap(),
+            }
+        }
+    }
+
+    pub fn get_or_init<G: FnOnce() -> T>(&self, init: G) -> Result<&T, PoisonError> {
+        self.get_with(0, |core| {
+            core.value = Some(init());
+            core.func = None;
+        })
+    }
+
+    pub fn get_timeout(&self, timeout: Duration) -> Result<Option<&T>, PoisonError> {
+        let deadline = Instant::now() + timeout;
+        self.get_until(0, Some(deadline), |core| -> Result<(), ()> {
+            core.value = Some(core.func.take().unwrap()());
+            Ok(())
+        }).map_err(|_| PoisonError)
+    }
+
+    fn try_calculate_with<C, E>(&self, priority: u8, calculate: C) -> Result<&T, TryError<E>>
+        where C: FnOnce(&mut ThreadsafeMemoCore<T, F>) -> Result<(), E>
+    {
+        self.get_until(priority, None, calculate).map(|value| value.unwrap())
+    }
+
+    // A failed calculation leaves the memo uncalculated so that a later caller
+    // can try again. Returns `Ok(None)` only if the deadline passes while
+    // waiting for another thread.
+    fn get_until<C, E>(&self, priority: u8, deadline: Option<Instant>, calculate: C) -> Result<Option<&T>, TryError<E>>
+        where C: FnOnce(&mut ThreadsafeMemoCore<T, F>) -> Result<(), E>
+    {
         let mut state = self.state.load(Ordering::Acquire);
         loop {
             match state {
-                POISONED => return Err(()),
-                CALCULATED => return unsafe { Ok((*self.core.get()).value.as_ref().unwrap()) },
+                POISONED => return Err(TryError::Poisoned),
+                CALCULATED => return unsafe { Ok((*self.core.get()).value.as_ref()) },
                 UNCALCULATED => {
                     if let Err(new_state) = self.state.compare_exchange(UNCALCULATED,
                                                                         WORKING,
@@ -79,286 +346,3217 @@ impl<'a, T, F: FnOnce() -> T> ThreadsafeMemo<T, F> {
                         state = new_state;
                         continue;
                     }
-                    let mut finish = Finish {
-                        destination_state: POISONED,
-                        state: &self.state,
+                    let mut finish = self.finish();
+                    let abort_on_unwind = AbortOnUnwind {
+                        hook: self.on_poison,
                     };
                     let core = unsafe { &mut *self.core.get() };
-                    core.value = Some(core.func.take().unwrap()());
-                    let out = Ok(core.value.as_ref().unwrap());
+                    let calculated = calculate(core);
+                    mem::forget(abort_on_unwind);
+                    if let Err(err) = calculated {
+                        finish.destination_state = UNCALCULATED;
+                        return Err(TryError::Failed(err));
+                    }
+                    let out = Ok(core.value.as_ref());
                     finish.destination_state = CALCULATED;
                     return out;
                 },
                 _ => {
-                    assert_eq!(state & STATE_MASK, WORKING);
-                    let mut spin_state = SpinState {
-                        thread: Some(thread::current()),
-                        signaled: AtomicBool::new(false),
-                        next: ptr::null_mut(),
+                    state = match self.block(priority, state, deadline) {
+                        Some(new_state) => new_state,
+                        None => return Ok(None),
                     };
-                    let spin_state_ptr = &mut spin_state as *mut SpinState as usize;
-                    assert_eq!(spin_state_ptr & STATE_MASK, 0);
-
-                    while state & STATE_MASK == WORKING {
-                        spin_state.next = (state & !STATE_MASK) as *mut SpinState;
-
-                        if let Err(new_state) = self.state.compare_exchange(state,
-                                                                            spin_state_ptr | WORKING,
-                                                                            Ordering::AcqRel,
-                                                                            Ordering::Acquire) {
-                            state = new_state;
-                            continue;
-                        }
+                }
+            }
+        }
+    }
 
-                        while !spin_state.signaled.load(Ordering::Acquire) {
-                            thread::park();
-                        }
+    pub fn try_begin_compute<'a>(&'a self) -> Option<ComputeGuard<'a, T, F, B>> {
+        if self.state.compare_exchange(UNCALCULATED, WORKING, Ordering::AcqRel, Ordering::Acquire).is_err() {
+            return None;
+        }
+        let mut finish = self.finish();
+        finish.destination_state = UNCALCULATED;
+        Some(ComputeGuard {
+            memo: self,
+            finish,
+        })
+    }
 
-                        state = self.state.load(Ordering::Acquire);
-                        break;
+    // Calculates if nobody else is, but never waits for another thread.
+    pub fn try_get_or_skip(&self) -> TryGetResult<&T> {
+        loop {
+            match self.state.load(Ordering::Acquire) {
+                POISONED => return TryGetResult::Poisoned,
+                CALCULATED => return TryGetResult::Computed(unsafe { (*self.core.get()).value.as_ref().unwrap() }),
+                UNCALCULATED => {
+                    if let Some(guard) = self.try_begin_compute() {
+                        return TryGetResult::Computed(guard.compute());
                     }
+                },
+                _ => return TryGetResult::InProgress,
+            }
+        }
+    }
+
+    // Unlike `get`, this never calculates, so an uncalculated memo returns
+    // `WaitResult::Uncalculated` at once instead of waiting for a producer that
+    // may never come.
+    pub fn wait(&self) -> Result<WaitResult, PoisonError> {
+        self.wait_until(None)
+    }
+
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<WaitResult, PoisonError> {
+        self.wait_until(Some(Instant::now() + timeout))
+    }
+
+    fn wait_until(&self, deadline: Option<Instant>) -> Result<WaitResult, PoisonError> {
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            match state {
+                POISONED => return Err(PoisonError),
+                CALCULATED => return Ok(WaitResult::Ready),
+                UNCALCULATED => return Ok(WaitResult::Uncalculated),
+                _ => {
+                    state = match self.block(0, state, deadline) {
+                        Some(new_state) => new_state,
+                        None => return Ok(WaitResult::TimedOut),
+                    };
+                }
+            }
+        }
+    }
+
+    // Waits for the working thread to finish and returns the new state, or
+    // `None` if the deadline passed first.
+    fn block(&self, priority: u8, mut state: usize, deadline: Option<Instant>) -> Option<usize> {
+        assert_eq!(state & STATE_MASK, WORKING);
+        // Enqueueing rechecks the state with a CAS, so finishing while we spin
+        // can't cause a missed wakeup.
+        for _ in 0..self.spin_limit {
+            hint::spin_loop();
+            state = self.state.load(Ordering::Acquire);
+            if state & STATE_MASK != WORKING {
+                return Some(state);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return None;
+            }
+        }
+        let blocker = B::current();
+        match deadline {
+            None => {
+                let mut spin_state = SpinState::new(priority, blocker.clone());
+                unsafe { self.enqueue(state, &mut spin_state, &blocker, None) }
+            },
+            Some(deadline) => {
+                // A node that times out stays in the queue after we leave, so
+                // it has to outlive this stack frame.
+                let spin_state = Box::into_raw(Box::new(SpinState::new(priority, blocker.clone())));
+                let new_state = unsafe { self.enqueue(state, spin_state, &blocker, Some(deadline)) };
+                if new_state.is_some() {
+                    drop(unsafe { Box::from_raw(spin_state) });
+                }
+                new_state
+            },
+        }
+    }
+
+    // Returns `None` if the deadline passed while queued, in which case the node
+    // has been abandoned to the finishing thread.
+    unsafe fn enqueue(&self, mut state: usize, spin_state: *mut SpinState<B>, blocker: &B, deadline: Option<Instant>) -> Option<usize> {
+        let spin_state_ptr = spin_state as usize;
+        assert_eq!(spin_state_ptr & STATE_MASK, 0);
+
+        while state & STATE_MASK == WORKING {
+            (*spin_state).next = (state & !STATE_MASK) as *mut SpinState<B>;
+
+            if let Err(new_state) = self.state.compare_exchange(state,
+                                                                spin_state_ptr | WORKING,
+                                                                Ordering::AcqRel,
+                                                                Ordering::Acquire) {
+                state = new_state;
+                continue;
+            }
+
+            while (*spin_state).status.load(Ordering::Acquire) != SIGNALED {
+                let now = Instant::now();
+                match deadline {
+                    Some(deadline) if now < deadline => blocker.block_timeout(deadline - now),
+                    Some(_) => {
+                        if (*spin_state).status.compare_exchange(QUEUED,
+                                                                 ABANDONED,
+                                                                 Ordering::AcqRel,
+                                                                 Ordering::Acquire).is_ok() {
+                            return None;
+                        }
+                        // the finishing thread already claimed us and is about to signal
+                        blocker.block();
+                    },
+                    None => blocker.block(),
                 }
             }
+
+            return Some(self.state.load(Ordering::Acquire));
         }
+        Some(state)
     }
 
-    pub fn try_get(&self) -> Result<Option<&T>, ()> {
+    pub fn try_get(&self) -> Result<Option<&T>, PoisonError> {
         match self.state.load(Ordering::Acquire) {
-            POISONED => Err(()),
+            POISONED => Err(PoisonError),
             CALCULATED => unsafe { Ok((*self.core.get()).value.as_ref()) },
             _ => Ok(None)
         }
     }
 
-    pub fn take(self) -> Result<T, ()> {
-        match (atomic_usize_into_inner(self.state), unsafe { self.core.into_inner() }) {
-            (POISONED, _) => Err(()),
-            (UNCALCULATED, ThreadsafeMemoCore { func: Some(func), value: None }) => Ok(func()),
-            (CALCULATED, ThreadsafeMemoCore { func: None, value: Some(value) }) => Ok(value),
-            _ => panic!("ThreadsafeMemo had an invalid state!")
+    /// Returns the value without checking the state.
+    ///
+    /// # Safety
+    ///
+    /// A call that returned the value, such as `get` or `try_get`, must have
+    /// happened before this one, either on this thread or on one that has
+    /// synchronized with it since, and the memo must not have been mutably
+    /// borrowed in between. A calculated memo can't be reset through `&self`,
+    /// so it is then still calculated, and the earlier acquire load already
+    /// made the value visible.
+    pub unsafe fn get_unchecked(&self) -> &T {
+        debug_assert_eq!(self.state.load(Ordering::Relaxed), CALCULATED);
+        (*self.core.get()).value.as_ref().unwrap_unchecked()
+    }
+
+    /// Like `try_get`, but loads the state with the given ordering.
+    ///
+    /// # Safety
+    ///
+    /// `Acquire` and `SeqCst` are always fine. With `Relaxed`, the caller must
+    /// already have synchronized with the thread that calculated the memo, for
+    /// example by joining it; otherwise the value may be read while it is
+    /// still being written. `try_get_relaxed` is the safe way to avoid paying
+    /// for acquire loads that don't find a value.
+    pub unsafe fn try_get_with(&self, ordering: Ordering) -> Result<Option<&T>, PoisonError> {
+        debug_assert!(matches!(ordering, Ordering::Relaxed | Ordering::Acquire | Ordering::SeqCst),
+                      "{:?} is not a load ordering!", ordering);
+        match self.state.load(ordering) {
+            POISONED => Err(PoisonError),
+            CALCULATED => Ok((*self.core.get()).value.as_ref()),
+            _ => Ok(None)
+        }
+    }
+
+    // Unlike `try_get`, poisoned memos just have no value.
+    pub fn value(&self) -> Option<&T> {
+        self.try_get().unwrap_or(None)
+    }
+
+    pub fn try_get_cloned(&self) -> Result<Option<T>, PoisonError> where T: Clone {
+        self.try_get().map(|value| value.cloned())
+    }
+
+    // Every transition into CALCULATED happens through `Finish`, whose swap is a
+    // release operation sequenced after the value was written. An acquire fence
+    // after a relaxed load that observes that swap synchronizes with it just as
+    // an acquire load would, so the fence only has to be paid when we are about
+    // to hand out a reference.
+    pub fn try_get_relaxed(&self) -> Result<Option<&T>, PoisonError> {
+        match self.state.load(Ordering::Relaxed) {
+            POISONED => Err(PoisonError),
+            CALCULATED => {
+                atomic::fence(Ordering::Acquire);
+                unsafe { Ok((*self.core.get()).value.as_ref()) }
+            },
+            _ => Ok(None)
+        }
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.state.load(Ordering::Acquire) == POISONED
+    }
+
+    pub fn state(&self) -> MemoState {
+        decode_state(self.state.load(Ordering::Acquire))
+    }
+
+    // Exclusive access already orders us after every other access, so relaxed
+    // operations stand in for `AtomicUsize::get_mut`, which loom lacks. The
+    // calculation still goes through `Finish`, so that the callbacks run even
+    // if the closure panics.
+    pub fn get_mut(&mut self) -> Result<&mut T, PoisonError> {
+        if self.state.load(Ordering::Relaxed) == UNCALCULATED {
+            self.state.store(WORKING, Ordering::Relaxed);
+            let mut finish = self.finish();
+            let abort_on_unwind = AbortOnUnwind {
+                hook: self.on_poison,
+            };
+            let core = unsafe { &mut *self.core.get() };
+            core.value = Some(core.func.take().unwrap()());
+            mem::forget(abort_on_unwind);
+            finish.destination_state = CALCULATED;
+        }
+        match self.state.load(Ordering::Relaxed) {
+            CALCULATED => Ok(self.core.get_mut().value.as_mut().unwrap()),
+            _ => Err(PoisonError),
+        }
+    }
+
+    // Exclusive access means nobody can be waiting or borrowing the old value,
+    // so this works from any state without touching the waiter queue.
+    pub fn reset(&mut self, func: F) {
+        self.state.store(UNCALCULATED, Ordering::Relaxed);
+        self.warming.store(false, Ordering::Relaxed);
+        *self.core.get_mut() = ThreadsafeMemoCore {
+            func: Some(func),
+            value: None,
+        };
+    }
+
+    // Memos built with `empty` rest uncalculated without a closure, `get_try`
+    // keeps the error that poisoned a memo, and `get_retrying` keeps the
+    // closure that may rearm one, so only a value alongside a closure is
+    // broken for those states.
+    pub fn debug_check(&mut self) {
+        let state = self.state.load(Ordering::Relaxed);
+        let core = self.core.get_mut();
+        match state {
+            UNCALCULATED => debug_assert!(core.value.is_none(),
+                                          "uncalculated ThreadsafeMemo has a value!"),
+            CALCULATED => debug_assert!(core.func.is_none() && core.value.is_some(),
+                                        "calculated ThreadsafeMemo is missing its value!"),
+            POISONED => debug_assert!(core.func.is_none() || core.value.is_none(),
+                                      "poisoned ThreadsafeMemo has both a closure and a value!"),
+            _ => debug_assert!(false, "uniquely owned ThreadsafeMemo is still working!"),
+        }
+    }
+
+    // `Finish` always stores one of the three resting states, so a uniquely
+    // owned memo can only be working if that guard was leaked. Memos built with
+    // `empty` rest uncalculated without a closure until they are initialized.
+    // Whatever the match doesn't move out of the core is dropped when it ends,
+    // so the memo never drops the returned value and never leaks the rest.
+    pub fn take(self) -> Result<T, PoisonError> {
+        self.take_with(|| panic!("uniquely owned ThreadsafeMemo is still working!"))
+    }
+
+    // Same as `take`, but a leaked `Finish` reports an error instead of
+    // panicking. Working is otherwise unreachable once the memo is owned.
+    pub fn take_or_compute(self) -> Result<T, PoisonError> {
+        self.take_with(|| Err(PoisonError))
+    }
+
+    fn take_with<W: FnOnce() -> Result<T, PoisonError>>(self, working: W) -> Result<T, PoisonError> {
+        let hook = self.on_poison;
+        match (decode_state(self.state.into_inner()), self.core.into_inner()) {
+            (MemoState::Poisoned, _) => Err(PoisonError),
+            (MemoState::Working, _) => working(),
+            (MemoState::Uncalculated, ThreadsafeMemoCore { func: Some(func), .. }) => {
+                let abort_on_unwind = AbortOnUnwind {
+                    hook,
+                };
+                let value = func();
+                mem::forget(abort_on_unwind);
+                Ok(value)
+            },
+            (MemoState::Uncalculated, _) => panic!("uncalculated ThreadsafeMemo is missing its closure!"),
+            (MemoState::Calculated, ThreadsafeMemoCore { value: Some(value), .. }) => Ok(value),
+            (MemoState::Calculated, _) => panic!("calculated ThreadsafeMemo is missing its value!"),
+        }
+    }
+
+    pub fn try_take(self) -> Result<Option<T>, PoisonError> {
+        match (decode_state(self.state.into_inner()), self.core.into_inner()) {
+            (MemoState::Poisoned, _) => Err(PoisonError),
+            (MemoState::Uncalculated, _) => Ok(None),
+            (MemoState::Calculated, ThreadsafeMemoCore { value: Some(value), .. }) => Ok(Some(value)),
+            (MemoState::Calculated, _) => panic!("calculated ThreadsafeMemo is missing its value!"),
+            (MemoState::Working, _) => panic!("uniquely owned ThreadsafeMemo is still working!"),
+        }
+    }
+
+    // Poisoned memos have nothing to convert, so unlike the other direction
+    // this is fallible.
+    pub fn into_memo(self) -> Result<Memo<T, F>, PoisonError> {
+        match (decode_state(self.state.into_inner()), self.core.into_inner()) {
+            (MemoState::Poisoned, _) => Err(PoisonError),
+            (MemoState::Uncalculated, ThreadsafeMemoCore { func, .. }) => Ok(Memo::from_parts(func, None)),
+            (MemoState::Calculated, ThreadsafeMemoCore { value: Some(value), .. }) => Ok(Memo::with_value(value)),
+            (MemoState::Calculated, _) => panic!("calculated ThreadsafeMemo is missing its value!"),
+            (MemoState::Working, _) => panic!("uniquely owned ThreadsafeMemo is still working!"),
+        }
+    }
+
+    pub fn unpoison(&self, func: F) -> bool {
+        self.unpoison_with(|| func)
+    }
+
+    // `make` only runs for the caller that wins the race. If it panics, the
+    // memo stays poisoned.
+    pub fn unpoison_with<G: FnOnce() -> F>(&self, make: G) -> bool {
+        self.replace_core(|state| state == POISONED, || ThreadsafeMemoCore {
+            func: Some(make()),
+            value: None,
+        }, UNCALCULATED)
+    }
+
+    pub fn unpoison_with_value(&self, value: T) -> bool {
+        self.replace_core(|state| state == POISONED, || ThreadsafeMemoCore {
+            func: None,
+            value: Some(value),
+        }, CALCULATED)
+    }
+
+    // A calculated memo may have outstanding borrows of its value, so only
+    // uncalculated and poisoned memos can be reset through `&self`.
+    pub fn reset_atomic(&self, func: Option<F>, value: Option<T>) -> Result<(), ()> {
+        let (core, destination_state) = match (func, value) {
+            (Some(func), None) => (ThreadsafeMemoCore {
+                func: Some(func),
+                value: None,
+            }, UNCALCULATED),
+            (None, Some(value)) => (ThreadsafeMemoCore {
+                func: None,
+                value: Some(value),
+            }, CALCULATED),
+            _ => return Err(()),
+        };
+        if self.replace_core(|state| state == POISONED || state == UNCALCULATED, || core, destination_state) {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+
+    fn finish<'a>(&'a self) -> Finish<'a, T, F, B> {
+        Finish {
+            destination_state: POISONED,
+            memo: self,
+        }
+    }
+
+    // Runs `g` with the outcome once the memo is calculated or poisoned, right
+    // away if it already is. Otherwise `g` is queued and run by whichever
+    // thread finishes the calculation, after the new state is published and
+    // the waiters are woken. Callbacks don't hold any lock, so they may use
+    // the memo and register more callbacks, but they do delay the return of
+    // the `get` that ran the calculation. A callback that panics while the
+    // calculation itself is unwinding aborts the process. Queued callbacks
+    // are dropped unrun if the memo is taken or dropped first.
+    pub fn on_complete<G>(&self, g: G) where G: FnOnce(Result<&T, PoisonError>) + Send + 'static {
+        let mut callbacks = self.callbacks.list().lock().unwrap();
+        // `Finish` publishes the state before draining under this lock, so a
+        // callback is either seen by the drain or sees the finished state.
+        let state = self.state.load(Ordering::Acquire);
+        match state {
+            CALCULATED | POISONED => {
+                drop(callbacks);
+                g(self.outcome(state));
+            },
+            _ => callbacks.push(Box::new(g)),
+        }
+    }
+
+    fn outcome(&self, state: usize) -> Result<&T, PoisonError> {
+        match state {
+            CALCULATED => unsafe { Ok((*self.core.get()).value.as_ref().unwrap()) },
+            _ => Err(PoisonError),
+        }
+    }
+
+    fn run_callbacks(&self, state: usize) {
+        run_callbacks(self.callbacks.drain(), self.outcome(state));
+    }
+
+    // Only `E::clone` runs under this lock, and a clone that panics leaves
+    // nothing half-done, so a poisoned lock is as good as any other.
+    fn lock_error_readers(&self) -> MutexGuard<'_, ()> {
+        self.error_readers.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn replace_core<R, M>(&self, replaceable: R, make_core: M, destination_state: usize) -> bool
+        where R: Fn(usize) -> bool, M: FnOnce() -> ThreadsafeMemoCore<T, F>
+    {
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            if !replaceable(state) {
+                return false;
+            }
+            match self.state.compare_exchange(state,
+                                              WORKING,
+                                              Ordering::AcqRel,
+                                              Ordering::Acquire) {
+                Ok(_) => break,
+                Err(new_state) => state = new_state,
+            }
+        }
+        let mut finish = self.finish();
+        let core = make_core();
+        // `get_try` reads a stored error under this lock without claiming the
+        // memo, so the old core has to be swapped out under it too. It's
+        // dropped outside the lock in case its destructor uses the memo.
+        let old = {
+            let _readers = self.lock_error_readers();
+            unsafe { mem::replace(&mut *self.core.get(), core) }
+        };
+        drop(old);
+        if destination_state == UNCALCULATED {
+            self.warming.store(false, Ordering::Release);
+        }
+        finish.destination_state = destination_state;
+        true
+    }
+}
+
+impl<T, F: FnOnce() -> T + Clone, B: Blocker> ThreadsafeMemo<T, F, B> {
+    // The budget belongs to the call, so a memo that was reset or unpoisoned
+    // starts over with whatever budget the next caller brings.
+    pub fn get_retrying(&self, mut budget: usize) -> Result<&T, PoisonError> {
+        loop {
+            let got = panic::catch_unwind(AssertUnwindSafe(|| {
+                self.get_with(0, |core| {
+                    let func = core.func.clone().unwrap();
+                    core.value = Some(func());
+                    core.func = None;
+                })
+            }));
+            if let Ok(Ok(value)) = got {
+                return Ok(value);
+            }
+            if !self.rearm(&mut budget) {
+                return Err(PoisonError);
+            }
+        }
+    }
+
+    // Only the caller that wins the POISONED -> WORKING transition spends its
+    // budget, so concurrent callers that saw the same panic don't each spend
+    // theirs on it.
+    fn rearm(&self, budget: &mut usize) -> bool {
+        match self.state.compare_exchange(POISONED,
+                                          WORKING,
+                                          Ordering::AcqRel,
+                                          Ordering::Acquire) {
+            Ok(_) => {
+                let mut finish = self.finish();
+                let core = unsafe { &*self.core.get() };
+                if core.func.is_none() || *budget == 0 {
+                    return false;
+                }
+                *budget -= 1;
+                finish.destination_state = UNCALCULATED;
+                true
+            },
+            Err(_) => true,
+        }
+    }
+}
+
+impl<T, F, B> ThreadsafeMemo<T, F, B>
+    where T: Send + Sync + 'static, F: FnOnce() -> T + Send + Sync + 'static, B: Blocker + Send + 'static
+{
+    pub fn warm(self: &Arc<Self>) {
+        if self.state.load(Ordering::Acquire) != UNCALCULATED ||
+           self.warming.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let memo = self.clone();
+        thread::spawn(move || {
+            let _ = memo.get();
+        });
+    }
+}
+
+impl<T, E, F: FnOnce() -> Result<T, E>> ThreadsafeMemo<Result<T, E>, F> {
+    pub fn new_try(func: F) -> ThreadsafeMemo<Result<T, E>, F> {
+        ThreadsafeMemo::new(func)
+    }
+}
+
+impl<T, E: Clone, F: FnOnce() -> Result<T, E>, B: Blocker> ThreadsafeMemo<Result<T, E>, F, B> {
+    // An `Err` poisons the memo but is kept in it, so every caller sees the
+    // same error until the memo is unpoisoned. Readers clone the error under
+    // a lock that `unpoison` takes before replacing the core, so the error
+    // can't be freed under them; `E::clone` mustn't unpoison this memo. The
+    // other getters can't see the error and
+    // report a failed memo as `PoisonError`, as if its closure had panicked.
+    pub fn get_try(&self) -> Result<&T, TryError<E>> {
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            match state {
+                CALCULATED => return match unsafe { (*self.core.get()).value.as_ref().unwrap() } {
+                    Ok(value) => Ok(value),
+                    Err(err) => Err(TryError::Failed(err.clone())),
+                },
+                POISONED => {
+                    let _readers = self.lock_error_readers();
+                    state = self.state.load(Ordering::Acquire);
+                    if state != POISONED {
+                        continue;
+                    }
+                    return match unsafe { &(*self.core.get()).value } {
+                        Some(Err(err)) => Err(TryError::Failed(err.clone())),
+                        _ => Err(TryError::Poisoned),
+                    };
+                },
+                UNCALCULATED => {
+                    if let Err(new_state) = self.state.compare_exchange(UNCALCULATED, WORKING, Ordering::AcqRel, Ordering::Acquire) {
+                        state = new_state;
+                        continue;
+                    }
+                    let mut finish = self.finish();
+                    let abort_on_unwind = AbortOnUnwind {
+                        hook: self.on_poison,
+                    };
+                    let core = unsafe { &mut *self.core.get() };
+                    let result = core.func.take().unwrap()();
+                    mem::forget(abort_on_unwind);
+                    let value = core.value.insert(result);
+                    return match value {
+                        Ok(value) => {
+                            finish.destination_state = CALCULATED;
+                            Ok(value)
+                        },
+                        // `finish` still leaves the memo poisoned.
+                        Err(err) => Err(TryError::Failed(err.clone())),
+                    };
+                },
+                _ => state = self.block(0, state, None).unwrap(),
+            }
+        }
+    }
+}
+
+impl<T: Send, F: FnOnce() -> T + Send, B: Blocker> ThreadsafeMemo<T, F, B> {
+    // The source moves into the derived closure, so it is calculated at most
+    // once, by whichever thread first calculates the derived memo. A poisoned
+    // source poisons the derived memo too.
+    pub fn map<U, G: FnOnce(&T) -> U + Send>(self, g: G) -> ThreadsafeMemo<U, impl FnOnce() -> U> {
+        ThreadsafeMemo::new(move || {
+            let value = self.take_or_compute().expect("source ThreadsafeMemo was poisoned!");
+            g(&value)
+        })
+    }
+}
+
+impl<T, F: FnOnce() -> Arc<T>, B: Blocker> ThreadsafeMemo<Arc<T>, F, B> {
+    // These only bump the reference count, so every caller shares the one
+    // calculated allocation.
+    pub fn get_arc(&self) -> Result<Arc<T>, PoisonError> {
+        self.get().map(Arc::clone)
+    }
+
+    pub fn try_get_arc(&self) -> Result<Option<Arc<T>>, PoisonError> {
+        self.try_get().map(|value| value.map(Arc::clone))
+    }
+
+    pub fn get_or_insert_arc<G: FnOnce() -> Arc<T>>(&self, make: G) -> Result<Arc<T>, PoisonError> {
+        self.get_with(0, |core| {
+            core.func = None;
+            core.value = Some(make());
+        }).map(Arc::clone)
+    }
+}
+
+// A memo with neither a value nor a closure, like one whose closure was taken,
+// has nothing left to calculate, so it converts as poisoned.
+impl<T, F: FnOnce() -> T> From<Memo<T, F>> for ThreadsafeMemo<T, F> {
+    fn from(memo: Memo<T, F>) -> ThreadsafeMemo<T, F> {
+        let poisoned = memo.is_poisoned();
+        let (func, value) = memo.into_parts();
+        let state = match (&func, &value) {
+            (_, Some(_)) => CALCULATED,
+            (Some(_), None) if !poisoned => UNCALCULATED,
+            _ => POISONED,
+        };
+        let mut memo = ThreadsafeMemo::empty();
+        memo.state.store(state, Ordering::Relaxed);
+        *memo.core.get_mut() = ThreadsafeMemoCore {
+            func,
+            value,
+        };
+        memo
+    }
+}
+
+impl<T, F: FnOnce() -> T> From<AliasableMemo<T, F>> for ThreadsafeMemo<T, F> {
+    fn from(memo: AliasableMemo<T, F>) -> ThreadsafeMemo<T, F> {
+        ThreadsafeMemo::from(memo.into_memo())
+    }
+}
+
+impl<'a, T, F: FnOnce() -> T, B: Blocker> ComputeGuard<'a, T, F, B> {
+    pub fn compute(self) -> &'a T {
+        let core = unsafe { &mut *self.memo.core.get() };
+        let func = core.func.take().unwrap();
+        self.publish(func)
+    }
+
+    pub fn set(self, value: T) -> &'a T {
+        let core = unsafe { &mut *self.memo.core.get() };
+        core.func = None;
+        self.publish(move || value)
+    }
+
+    fn publish<G: FnOnce() -> T>(mut self, make: G) -> &'a T {
+        self.finish.destination_state = POISONED;
+        let abort_on_unwind = AbortOnUnwind {
+            hook: self.memo.on_poison,
+        };
+        let core = unsafe { &mut *self.memo.core.get() };
+        core.value = Some(make());
+        mem::forget(abort_on_unwind);
+        self.finish.destination_state = CALCULATED;
+        core.value.as_ref().unwrap()
+    }
+}
+
+// `Memo: From<T>` makes `Memo::try_from` ambiguous unless the value type is
+// named, so `ThreadsafeMemo::into_memo` is the easier spelling.
+impl<T, F: FnOnce() -> T, B: Blocker> TryFrom<ThreadsafeMemo<T, F, B>> for Memo<T, F> {
+    type Error = PoisonError;
+
+    fn try_from(memo: ThreadsafeMemo<T, F, B>) -> Result<Memo<T, F>, PoisonError> {
+        memo.into_memo()
+    }
+}
+
+/// Forces and clones the value of each memo in order.
+///
+/// This is not an atomic snapshot: without a global lock, a memo that is
+/// unpoisoned and recalculated concurrently may be read before or after the
+/// others change.
+pub fn snapshot_values<T: Clone, F: FnOnce() -> T, B: Blocker>(memos: &[Arc<ThreadsafeMemo<T, F, B>>]) -> Result<Vec<T>, PoisonError> {
+    memos.iter().map(|memo| memo.get().cloned()).collect()
+}
+
+/// Forces every memo, spreading them over one scoped thread per core.
+///
+/// A memo that another thread is already calculating is simply waited for. On
+/// failure, returns the indices of the memos that ended up poisoned, whether
+/// their closures panicked here or earlier.
+pub fn warm_all<T, F, B>(memos: &[Arc<ThreadsafeMemo<T, F, B>>]) -> Result<(), Vec<usize>>
+    where T: Send + Sync, F: FnOnce() -> T + Send + Sync, B: Blocker + Send
+{
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get()).min(memos.len());
+    let next = AtomicUsize::new(0);
+    let mut poisoned = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..workers).map(|_| scope.spawn(|| {
+            let mut poisoned = Vec::new();
+            loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let memo = match memos.get(index) {
+                    Some(memo) => memo,
+                    None => return poisoned,
+                };
+                match panic::catch_unwind(AssertUnwindSafe(|| memo.force())) {
+                    Ok(Ok(())) => {  },
+                    Ok(Err(PoisonError)) | Err(_) => poisoned.push(index),
+                }
+            }
+        })).collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect::<Vec<_>>()
+    });
+    if poisoned.is_empty() {
+        Ok(())
+    } else {
+        poisoned.sort_unstable();
+        Err(poisoned)
+    }
+}
+
+// Waiters hand their blockers to whichever thread finishes the calculation.
+unsafe impl<T, F: FnOnce() -> T, B: Blocker> Sync for ThreadsafeMemo<T, F, B> where T: Sync, F: Sync, B: Send {  }
+impl<T, F: FnOnce() -> T, B: Blocker> UnwindSafe for ThreadsafeMemo<T, F, B> where T: UnwindSafe, F: UnwindSafe {  }
+impl<T, F: FnOnce() -> T, B: Blocker> RefUnwindSafe for ThreadsafeMemo<T, F, B> where T: RefUnwindSafe, F: RefUnwindSafe {  }
+
+impl Drop for AbortOnUnwind {
+    fn drop(&mut self) {
+        if let Some(hook) = self.hook {
+            (hook.log)();
+            (hook.abort)();
+        }
+    }
+}
+
+impl<T> Callbacks<T> {
+    #[cfg(not(feature = "loom"))]
+    const fn new() -> Callbacks<T> {
+        Callbacks {
+            list: AtomicPtr::new(ptr::null_mut()),
+            owned: PhantomData,
+        }
+    }
+
+    #[cfg(feature = "loom")]
+    fn new() -> Callbacks<T> {
+        Callbacks {
+            list: AtomicPtr::new(ptr::null_mut()),
+            owned: PhantomData,
+        }
+    }
+
+    // The list is never freed before the memo, so whichever allocation wins
+    // the race stays valid for as long as `self`.
+    fn list(&self) -> &Mutex<Vec<Callback<T>>> {
+        let mut list = self.list.load(Ordering::Acquire);
+        if list.is_null() {
+            let new = Box::into_raw(Box::new(Mutex::new(Vec::new())));
+            list = match self.list.compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => new,
+                Err(existing) => {
+                    drop(unsafe { Box::from_raw(new) });
+                    existing
+                },
+            };
+        }
+        unsafe { &*list }
+    }
+
+    // Finding no list has to be a read-modify-write rather than a load. An
+    // `on_complete` whose allocation comes later in the pointer's modification
+    // order then reads from it, which orders it after the state swap that
+    // preceded the drain, so it sees the finished state and runs its own
+    // callback.
+    fn drain(&self) -> Vec<Callback<T>> {
+        match self.list.compare_exchange(ptr::null_mut(), ptr::null_mut(), Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => Vec::new(),
+            Err(list) => mem::take(&mut *unsafe { &*list }.lock().unwrap()),
+        }
+    }
+}
+
+impl<T> Drop for Callbacks<T> {
+    fn drop(&mut self) {
+        let list = self.list.load(Ordering::Relaxed);
+        if !list.is_null() {
+            drop(unsafe { Box::from_raw(list) });
+        }
+    }
+}
+
+impl<B: Blocker> SpinState<B> {
+    fn new(priority: u8, blocker: B) -> SpinState<B> {
+        SpinState {
+            blocker: Some(blocker),
+            priority,
+            status: AtomicUsize::new(QUEUED),
+            next: ptr::null_mut(),
+        }
+    }
+}
+
+impl<'a, T, F: FnOnce() -> T, B: Blocker> Drop for Finish<'a, T, F, B> {
+    fn drop(&mut self) {
+        // Acquire as well, since waiters publish their nodes with the CAS that
+        // queues them and we read those nodes below.
+        let state = self.memo.state.swap(self.destination_state, Ordering::AcqRel);
+        assert_eq!(state & STATE_MASK, WORKING);
+
+        let head = (state & !STATE_MASK) as *mut SpinState<B>;
+        for spin_state in unsafe { waiters_by_priority(head) } {
+            let spin_state = unsafe { &mut *spin_state };
+            match spin_state.status.compare_exchange(QUEUED,
+                                                     CLAIMED,
+                                                     Ordering::AcqRel,
+                                                     Ordering::Acquire) {
+                Ok(_) => {
+                    let blocker = spin_state.blocker.take().unwrap();
+                    spin_state.status.store(SIGNALED, Ordering::Release);
+                    blocker.unblock();
+                },
+                Err(status) => {
+                    assert_eq!(status, ABANDONED);
+                    drop(unsafe { Box::from_raw(spin_state) });
+                },
+            }
+        }
+
+        // Uncalculated memos keep their callbacks for the next calculation.
+        if self.destination_state != UNCALCULATED {
+            self.memo.run_callbacks(self.destination_state);
+        }
+    }
+}
+
+// Every callback runs even if an earlier one panics; the first panic is
+// resumed once they are done.
+fn run_callbacks<T>(callbacks: Vec<Callback<T>>, outcome: Result<&T, PoisonError>) {
+    let mut payload = None;
+    for callback in callbacks {
+        if let Err(err) = panic::catch_unwind(AssertUnwindSafe(|| callback(outcome))) {
+            payload = payload.or(Some(err));
+        }
+    }
+    if let Some(payload) = payload {
+        panic::resume_unwind(payload);
+    }
+}
+
+// Waiters stay queued until signaled and abandoned nodes are only freed by us,
+// so every node in the queue remains valid until we wake or free it. The queue
+// is newest first, so it is reversed before the stable sort to wake waiters of
+// equal priority in the order they arrived.
+unsafe fn waiters_by_priority<B>(mut head: *mut SpinState<B>) -> Vec<*mut SpinState<B>> {
+    let mut waiters = Vec::new();
+    while !head.is_null() {
+        waiters.push(head);
+        head = (*head).next;
+    }
+    waiters.reverse();
+    waiters.sort_by_key(|&spin_state| Reverse((*spin_state).priority));
+    waiters
+}
+
+#[cfg(test)]
+#[allow(unused_assignments)]
+mod tests {
+    mod new {
+        use super::super::ThreadsafeMemo;
+
+        #[test]
+        fn get() {
+            let mut times = 0;
+            {
+                let memo = ThreadsafeMemo::new(|| {
+                    times += 1;
+                    212
+                });
+                assert_eq!(*memo.get().unwrap(), 212);
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        fn try_get() {
+            let mut times = 0;
+            {
+                let memo = ThreadsafeMemo::new(|| {
+                    times += 1;
+                    212
+                });
+                assert!(memo.try_get().unwrap().is_none());
+            }
+            assert_eq!(times, 0);
+        }
+
+        #[test]
+        fn take() {
+            let mut times = 0;
+            {
+                let memo = ThreadsafeMemo::new(|| {
+                    times += 1;
+                    212
+                });
+                assert_eq!(memo.take().unwrap(), 212);
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        fn try_take() {
+            let mut times = 0;
+            {
+                let memo = ThreadsafeMemo::new(|| {
+                    times += 1;
+                    212
+                });
+                assert!(memo.try_take().unwrap().is_none());
+            }
+            assert_eq!(times, 0);
+        }
+
+        #[test]
+        fn get_mut() {
+            let mut times = 0;
+            {
+                let mut memo = ThreadsafeMemo::new(|| {
+                    times += 1;
+                    vec![212]
+                });
+                memo.get_mut().unwrap().push(213);
+                assert_eq!(*memo.get().unwrap(), vec![212, 213]);
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        #[allow(unused_must_use)]
+        fn get_mut_poison() {
+            let mut memo = ThreadsafeMemo::new(|| -> u32 { panic!() });
+            ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+                memo.get_mut();
+            })).unwrap_err();
+            memo.get_mut().unwrap_err();
+            memo.get().unwrap_err();
+        }
+
+        #[test]
+        fn get_get() {
+            let mut times = 0;
+            {
+                let memo = ThreadsafeMemo::new(|| {
+                    times += 1;
+                    212 + times - 1
+                });
+                assert_eq!(*memo.get().unwrap(), 212);
+                assert_eq!(*memo.get().unwrap(), 212);
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        fn get_try_get() {
+            let mut times = 0;
+            {
+                let memo = ThreadsafeMemo::new(|| {
+                    times += 1;
+                    212 + times - 1
+                });
+                assert_eq!(*memo.get().unwrap(), 212);
+                assert_eq!(*memo.try_get().unwrap().unwrap(), 212);
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        fn get_take() {
+            let mut times = 0;
+            {
+                let memo = ThreadsafeMemo::new(|| {
+                    times += 1;
+                    212 + times - 1
+                });
+                assert_eq!(*memo.get().unwrap(), 212);
+                assert_eq!(memo.take().unwrap(), 212);
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        fn get_try_take() {
+            let mut times = 0;
+            {
+                let memo = ThreadsafeMemo::new(|| {
+                    times += 1;
+                    212 + times - 1
+                });
+                assert_eq!(*memo.get().unwrap(), 212);
+                assert_eq!(memo.try_take().unwrap().unwrap(), 212);
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        fn get_get_mut() {
+            let mut times = 0;
+            {
+                let mut memo = ThreadsafeMemo::new(|| {
+                    times += 1;
+                    212 + times - 1
+                });
+                assert_eq!(*memo.get().unwrap(), 212);
+                *memo.get_mut().unwrap() += 1;
+                assert_eq!(*memo.get().unwrap(), 213);
+            }
+            assert_eq!(times, 1);
+        }
+    }
+
+    mod with_value {
+        use super::super::ThreadsafeMemo;
+
+        #[test]
+        fn get() {
+            let mut memo = ThreadsafeMemo::new(|| { 200 });
+            memo = ThreadsafeMemo::with_value(212);
+            assert_eq!(*memo.get().unwrap(), 212);
+        }
+
+        #[test]
+        fn try_get() {
+            let mut memo = ThreadsafeMemo::new(|| { 200 });
+            memo = ThreadsafeMemo::with_value(212);
+            assert_eq!(*memo.try_get().unwrap().unwrap(), 212);
+        }
+
+        #[test]
+        fn take() {
+            let mut memo = ThreadsafeMemo::new(|| { 200 });
+            memo = ThreadsafeMemo::with_value(212);
+            assert_eq!(memo.take().unwrap(), 212);
+        }
+
+        #[test]
+        fn try_take() {
+            let mut memo = ThreadsafeMemo::new(|| { 200 });
+            memo = ThreadsafeMemo::with_value(212);
+            assert_eq!(memo.try_take().unwrap().unwrap(), 212);
+        }
+    }
+
+    mod retrying {
+        use super::super::ThreadsafeMemo;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        fn flaky(failures: usize, times: Arc<AtomicUsize>) -> impl FnOnce() -> u32 + Clone {
+            move || {
+                if times.fetch_add(1, Ordering::SeqCst) < failures {
+                    panic!();
+                }
+                212
+            }
+        }
+
+        #[test]
+        fn recovers() {
+            let times = Arc::new(AtomicUsize::new(0));
+            let memo = ThreadsafeMemo::new(flaky(2, times.clone()));
+            assert_eq!(*memo.get_retrying(3).unwrap(), 212);
+            assert_eq!(times.load(Ordering::SeqCst), 3);
+        }
+
+        #[test]
+        fn exhausted() {
+            let times = Arc::new(AtomicUsize::new(0));
+            let memo = ThreadsafeMemo::new(flaky(2, times.clone()));
+            memo.get_retrying(1).unwrap_err();
+            assert_eq!(times.load(Ordering::SeqCst), 2);
+            memo.get().unwrap_err();
+        }
+
+        #[test]
+        fn reset() {
+            let times = Arc::new(AtomicUsize::new(0));
+            let mut memo = ThreadsafeMemo::new(flaky(2, times.clone()));
+            memo.get_retrying(1).unwrap_err();
+            memo.reset(flaky(4, times.clone()));
+            assert_eq!(*memo.get_retrying(2).unwrap(), 212);
+            assert_eq!(times.load(Ordering::SeqCst), 5);
+        }
+
+        #[test]
+        fn calculated() {
+            let times = Arc::new(AtomicUsize::new(0));
+            let memo = ThreadsafeMemo::new(flaky(0, times.clone()));
+            assert_eq!(*memo.get_retrying(0).unwrap(), 212);
+            assert_eq!(*memo.get_retrying(0).unwrap(), 212);
+            assert_eq!(times.load(Ordering::SeqCst), 1);
+        }
+    }
+
+    mod eager_if {
+        use super::super::ThreadsafeMemo;
+
+        #[test]
+        fn eager() {
+            let mut times = 0;
+            {
+                let memo = ThreadsafeMemo::new_eager_if(|| {
+                    times += 1;
+                    212
+                }, true);
+                assert_eq!(*memo.try_get().unwrap().unwrap(), 212);
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        fn lazy() {
+            let mut times = 0;
+            {
+                let memo = ThreadsafeMemo::new_eager_if(|| {
+                    times += 1;
+                    212
+                }, false);
+                assert!(memo.try_get().unwrap().is_none());
+                assert_eq!(*memo.get().unwrap(), 212);
+            }
+            assert_eq!(times, 1);
+        }
+
+        #[test]
+        fn poison() {
+            let memo = ThreadsafeMemo::new_eager_if(|| -> u32 { panic!() }, true);
+            memo.try_get().unwrap_err();
+        }
+    }
+
+    mod abort_on_poison {
+        use super::super::{ThreadsafeMemo, PoisonHook};
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::panic;
+
+        static LOGGED: AtomicBool = AtomicBool::new(false);
+        static ABORTED: AtomicBool = AtomicBool::new(false);
+
+        fn log() {
+            LOGGED.store(true, Ordering::SeqCst);
+        }
+
+        fn abort() {
+            ABORTED.store(true, Ordering::SeqCst);
+        }
+
+        #[test]
+        fn calculated() {
+            let memo = ThreadsafeMemo::new_abort_on_poison(|| 212, log);
+            assert_eq!(*memo.get().unwrap(), 212);
+        }
+
+        #[test]
+        #[allow(unused_must_use)]
+        fn poison() {
+            let mut memo = ThreadsafeMemo::new_abort_on_poison(|| {
+                panic!();
+            }, log);
+            memo.on_poison = Some(PoisonHook {
+                log,
+                abort,
+            });
+            panic::catch_unwind(|| {
+                memo.get();
+            }).unwrap_err();
+            assert!(LOGGED.load(Ordering::SeqCst));
+            assert!(ABORTED.load(Ordering::SeqCst));
+        }
+
+        #[test]
+        fn take() {
+            static TAKE_ABORTED: AtomicBool = AtomicBool::new(false);
+
+            fn abort() {
+                TAKE_ABORTED.store(true, Ordering::SeqCst);
+            }
+
+            let mut memo = ThreadsafeMemo::new_abort_on_poison(|| -> u32 { panic!() }, log);
+            memo.on_poison = Some(PoisonHook {
+                log,
+                abort,
+            });
+            panic::catch_unwind(panic::AssertUnwindSafe(|| memo.take())).unwrap_err();
+            assert!(TAKE_ABORTED.load(Ordering::SeqCst));
+        }
+
+        #[test]
+        fn take_or_compute() {
+            static TAKE_OR_COMPUTE_ABORTED: AtomicBool = AtomicBool::new(false);
+
+            fn abort() {
+                TAKE_OR_COMPUTE_ABORTED.store(true, Ordering::SeqCst);
+            }
+
+            let mut memo = ThreadsafeMemo::new_abort_on_poison(|| -> u32 { panic!() }, log);
+            memo.on_poison = Some(PoisonHook {
+                log,
+                abort,
+            });
+            panic::catch_unwind(panic::AssertUnwindSafe(|| memo.take_or_compute())).unwrap_err();
+            assert!(TAKE_OR_COMPUTE_ABORTED.load(Ordering::SeqCst));
+        }
+
+        // Calculating from a destructor while an unrelated panic unwinds
+        // mustn't be mistaken for the closure panicking.
+        #[test]
+        fn calculated_while_unwinding() {
+            static UNWIND_ABORTED: AtomicBool = AtomicBool::new(false);
+
+            fn abort() {
+                UNWIND_ABORTED.store(true, Ordering::SeqCst);
+            }
+
+            fn hooked<T, F: FnOnce() -> T>(func: F) -> ThreadsafeMemo<T, F> {
+                let mut memo = ThreadsafeMemo::new_abort_on_poison(func, abort);
+                memo.on_poison = Some(PoisonHook {
+                    log: abort,
+                    abort,
+                });
+                memo
+            }
+
+            struct CalculateOnDrop;
+
+            impl Drop for CalculateOnDrop {
+                fn drop(&mut self) {
+                    assert_eq!(hooked(|| 212).get(), Ok(&212));
+                    assert_eq!(hooked(|| -> Result<u32, ()> { Ok(212) }).get_try(), Ok(&212));
+                    assert_eq!(hooked(|| 212).get_mut(), Ok(&mut 212));
+                    assert_eq!(hooked(|| 212).take(), Ok(212));
+                    assert_eq!(*hooked(|| 212).try_begin_compute().unwrap().compute(), 212);
+                }
+            }
+
+            panic::catch_unwind(|| {
+                let _calculate = CalculateOnDrop;
+                panic!();
+            }).unwrap_err();
+            assert!(!UNWIND_ABORTED.load(Ordering::SeqCst));
+        }
+    }
+
+    mod warm_all {
+        use super::super::{ThreadsafeMemo, warm_all};
+        use std::collections::HashSet;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+
+        type Memos = Vec<Arc<ThreadsafeMemo<u32, fn() -> u32>>>;
+
+        #[test]
+        fn once_each() {
+            let times: Arc<Vec<AtomicUsize>> = Arc::new((0..100).map(|_| AtomicUsize::new(0)).collect());
+            let threads = Arc::new(Mutex::new(HashSet::new()));
+            let memos: Vec<_> = (0..100).map(|i| {
+                let times = times.clone();
+                let threads = threads.clone();
+                Arc::new(ThreadsafeMemo::new(move || {
+                    times[i].fetch_add(1, Ordering::SeqCst);
+                    threads.lock().unwrap().insert(thread::current().id());
+                    212 + i
+                }))
+            }).collect();
+            memos[7].get().unwrap();
+            assert_eq!(warm_all(&memos), Ok(()));
+            assert!(times.iter().all(|times| times.load(Ordering::SeqCst) == 1));
+            assert!(memos.iter().enumerate().all(|(i, memo)| memo.try_get() == Ok(Some(&(212 + i)))));
+            assert_eq!(warm_all(&memos), Ok(()));
+            assert!(times.iter().all(|times| times.load(Ordering::SeqCst) == 1));
+            assert!(!threads.lock().unwrap().is_empty());
+        }
+
+        #[test]
+        fn poisoned() {
+            fn calculate() -> u32 { 212 }
+            fn fail() -> u32 { panic!() }
+            let memos: Memos = vec![
+                Arc::new(ThreadsafeMemo::new(calculate)),
+                Arc::new(ThreadsafeMemo::new(fail)),
+                Arc::new(ThreadsafeMemo::new(calculate)),
+                Arc::new(ThreadsafeMemo::new(fail)),
+            ];
+            assert_eq!(warm_all(&memos), Err(vec![1, 3]));
+            assert_eq!(memos[0].try_get(), Ok(Some(&212)));
+            assert_eq!(memos[2].try_get(), Ok(Some(&212)));
+            assert_eq!(warm_all(&memos), Err(vec![1, 3]));
+        }
+
+        #[test]
+        fn empty() {
+            let memos: Memos = vec![];
+            assert_eq!(warm_all(&memos), Ok(()));
+        }
+    }
+
+    mod snapshot_values {
+        use super::super::{ThreadsafeMemo, snapshot_values};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::panic;
+
+        #[test]
+        fn get() {
+            let times = Arc::new(AtomicUsize::new(0));
+            let memos: Vec<_> = (0..3).map(|i| {
+                let times = times.clone();
+                Arc::new(ThreadsafeMemo::new(move || {
+                    times.fetch_add(1, Ordering::SeqCst);
+                    212 + i
+                }))
+            }).collect();
+            assert_eq!(snapshot_values(&memos).unwrap(), vec![212, 213, 214]);
+            assert_eq!(snapshot_values(&memos).unwrap(), vec![212, 213, 214]);
+            assert_eq!(times.load(Ordering::SeqCst), 3);
+        }
+
+        fn calculate() -> u32 {
+            212
+        }
+
+        fn fail() -> u32 {
+            panic!();
+        }
+
+        #[test]
+        #[allow(unused_must_use)]
+        fn poison() {
+            let memos = vec![
+                Arc::new(ThreadsafeMemo::new(calculate as fn() -> u32)),
+                Arc::new(ThreadsafeMemo::new(fail as fn() -> u32)),
+            ];
+            panic::catch_unwind(|| {
+                memos[1].get();
+            }).unwrap_err();
+            snapshot_values(&memos).unwrap_err();
+        }
+    }
+
+    mod get_or_insert_arc {
+        use super::super::ThreadsafeMemo;
+        use std::sync::mpsc::channel;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::thread;
+
+        #[test]
+        fn shared() {
+            let (tx, rx) = channel();
+            let times = Arc::new(AtomicUsize::new(0));
+            let memo = Arc::new(ThreadsafeMemo::new(|| -> Arc<u32> { unreachable!() }));
+            for _ in 0..12 {
+                let tx = tx.clone();
+                let memo = memo.clone();
+                let times = times.clone();
+                thread::spawn(move || {
+                    let got = memo.get_or_insert_arc(|| {
+                        for _ in 0..3 {
+                            thread::yield_now();
+                        }
+                        times.fetch_add(1, Ordering::SeqCst);
+                        Arc::new(212)
+                    }).unwrap();
+                    tx.send(got).unwrap();
+                });
+            }
+            let first = rx.recv().unwrap();
+            assert_eq!(*first, 212);
+            for _ in 1..12 {
+                assert!(Arc::ptr_eq(&first, &rx.recv().unwrap()));
+            }
+            assert_eq!(times.load(Ordering::SeqCst), 1);
+        }
+
+        #[test]
+        fn calculated() {
+            let memo = ThreadsafeMemo::new(|| Arc::new(212));
+            let first = memo.get().unwrap().clone();
+            let got = memo.get_or_insert_arc(|| Arc::new(200)).unwrap();
+            assert!(Arc::ptr_eq(&first, &got));
+        }
+    }
+
+    mod warm {
+        use super::super::ThreadsafeMemo;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::thread;
+
+        #[test]
+        fn warm() {
+            let times = Arc::new(AtomicUsize::new(0));
+            let memo = {
+                let times = times.clone();
+                Arc::new(ThreadsafeMemo::new(move || {
+                    times.fetch_add(1, Ordering::SeqCst);
+                    212
+                }))
+            };
+            let warmers: Vec<_> = (0..2).map(|_| {
+                let memo = memo.clone();
+                thread::spawn(move || memo.warm())
+            }).collect();
+            for warmer in warmers {
+                warmer.join().unwrap();
+            }
+            while memo.try_get().unwrap().is_none() {
+                thread::yield_now();
+            }
+            memo.warm();
+            assert_eq!(*memo.get().unwrap(), 212);
+            assert_eq!(times.load(Ordering::SeqCst), 1);
+        }
+    }
+
+    mod priority {
+        use super::super::{ThreadsafeMemo, SpinState, waiters_by_priority};
+        use super::fifo::{Order, RecordingMemo, WAITER, queued};
+        use blocker::{Blocker, ParkBlocker};
+        use std::sync::Arc;
+        use std::thread;
+        use std::ptr;
+
+        fn spin_state(priority: u8, next: *mut SpinState<ParkBlocker>) -> SpinState<ParkBlocker> {
+            let mut spin_state = SpinState::new(priority, ParkBlocker::current());
+            spin_state.next = next;
+            spin_state
+        }
+
+        #[test]
+        fn order() {
+            let mut low = spin_state(1, ptr::null_mut());
+            let mut high = spin_state(9, &mut low);
+            let mut tied = spin_state(1, &mut high);
+            let mut mid = spin_state(5, &mut tied);
+            let order = unsafe { waiters_by_priority(&mut mid) };
+            assert_eq!(order, vec![&mut high as *mut _, &mut mid as *mut _, &mut low as *mut _, &mut tied as *mut _]);
+        }
+
+        #[test]
+        fn fifo() {
+            let mut third = spin_state(0, ptr::null_mut());
+            let mut second = spin_state(0, &mut third);
+            let mut first = spin_state(0, &mut second);
+            let order = unsafe { waiters_by_priority(&mut first) };
+            assert_eq!(order, vec![&mut third as *mut _, &mut second as *mut _, &mut first as *mut _]);
+        }
+
+        #[test]
+        fn mixed() {
+            let memo: Arc<RecordingMemo> = Arc::new(ThreadsafeMemo::with_blocker(|| 212));
+            let order = Order::default();
+            let guard = memo.try_begin_compute().unwrap();
+            let waiters: Vec<_> = (0..12).map(|id| {
+                let waiter = {
+                    let memo = memo.clone();
+                    let order = order.clone();
+                    thread::spawn(move || {
+                        WAITER.with(|waiter| *waiter.borrow_mut() = Some((id, order)));
+                        assert_eq!(memo.get_with_priority(id as u8 % 3), Ok(&212));
+                    })
+                };
+                while queued(&memo) != id + 1 {
+                    thread::yield_now();
+                }
+                waiter
+            }).collect();
+            guard.compute();
+            assert_eq!(*order.lock().unwrap(), [2, 5, 8, 11, 1, 4, 7, 10, 0, 3, 6, 9]);
+            for waiter in waiters {
+                waiter.join().unwrap();
+            }
+        }
+    }
+
+    mod reset_atomic {
+        use super::super::ThreadsafeMemo;
+        use std::panic;
+
+        fn calculate() -> u32 {
+            212
+        }
+
+        fn fail() -> u32 {
+            panic!();
+        }
+
+        #[test]
+        fn func() {
+            let memo = ThreadsafeMemo::new(fail as fn() -> u32);
+            memo.reset_atomic(Some(calculate), None).unwrap();
+            assert_eq!(*memo.get().unwrap(), 212);
+        }
+
+        #[test]
+        fn value() {
+            let memo = ThreadsafeMemo::new(fail as fn() -> u32);
+            memo.reset_atomic(None, Some(212)).unwrap();
+            assert_eq!(*memo.try_get().unwrap().unwrap(), 212);
+        }
+
+        #[test]
+        #[allow(unused_must_use)]
+        fn poisoned() {
+            let memo = ThreadsafeMemo::new(fail as fn() -> u32);
+            panic::catch_unwind(|| {
+                memo.get();
+            }).unwrap_err();
+            memo.reset_atomic(Some(calculate), None).unwrap();
+            assert_eq!(*memo.get().unwrap(), 212);
+        }
+
+        #[test]
+        fn calculated() {
+            let memo = ThreadsafeMemo::new(calculate as fn() -> u32);
+            memo.get().unwrap();
+            memo.reset_atomic(None, Some(200)).unwrap_err();
+            assert_eq!(*memo.get().unwrap(), 212);
+        }
+
+        #[test]
+        fn invalid() {
+            let memo = ThreadsafeMemo::new(calculate as fn() -> u32);
+            memo.reset_atomic(Some(calculate), Some(200)).unwrap_err();
+            memo.reset_atomic(None, None).unwrap_err();
+            assert_eq!(*memo.get().unwrap(), 212);
+        }
+    }
+
+    mod debug_check {
+        use super::super::{ThreadsafeMemo, WORKING};
+        use std::panic;
+        use std::sync::atomic::Ordering;
+
+        #[test]
+        #[allow(unused_must_use)]
+        fn valid() {
+            let mut memo = ThreadsafeMemo::new(|| 212);
+            memo.debug_check();
+            memo.get().unwrap();
+            memo.debug_check();
+
+            let mut memo = ThreadsafeMemo::new(|| -> u32 { panic!() });
+            panic::catch_unwind(|| {
+                memo.get();
+            }).unwrap_err();
+            memo.debug_check();
+        }
+
+        #[test]
+        fn failed() {
+            let mut memo = ThreadsafeMemo::new_try(|| -> Result<u32, &str> { Err("failed") });
+            assert!(memo.get_try().is_err());
+            assert!(memo.is_poisoned());
+            memo.debug_check();
+        }
+
+        #[test]
+        fn empty() {
+            let mut memo: ThreadsafeMemo<u32, fn() -> u32> = ThreadsafeMemo::empty();
+            memo.debug_check();
+            assert_eq!(memo.try_get_or_init(|| -> Result<u32, ()> { Ok(212) }), Ok(&212));
+            memo.debug_check();
+        }
+
+        #[test]
+        #[should_panic]
+        #[cfg(debug_assertions)]
+        fn missing_value() {
+            let mut memo = ThreadsafeMemo::new(|| 212);
+            memo.get().unwrap();
+            memo.core.get_mut().value = None;
+            memo.debug_check();
+        }
+
+        #[test]
+        #[should_panic]
+        #[cfg(debug_assertions)]
+        fn working() {
+            let mut memo = ThreadsafeMemo::new(|| 212);
+            memo.state.store(WORKING, Ordering::Relaxed);
+            memo.debug_check();
+        }
+    }
+
+    mod expect {
+        use super::super::ThreadsafeMemo;
+        use memo::PoisonError;
+        use std::thread;
+
+        #[test]
+        fn calculated() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            assert_eq!(*memo.expect("memo failed"), 212);
+        }
+
+        #[test]
+        fn poisoned() {
+            let memo: ThreadsafeMemo<u32, _> = ThreadsafeMemo::new(|| panic!());
+            assert!(thread::scope(|s| s.spawn(|| memo.get()).join()).is_err());
+            let err = thread::scope(|s| s.spawn(|| {
+                memo.expect("memo failed");
+            }).join()).unwrap_err();
+            assert_eq!(*err.downcast_ref::<String>().unwrap(), format!("memo failed: {}", PoisonError));
+        }
+    }
+
+    mod arc {
+        use super::super::ThreadsafeMemo;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::thread;
+
+        #[test]
+        fn into_arc() {
+            let memo = ThreadsafeMemo::new(|| 212).into_arc();
+            assert_eq!(memo.get_cloned(), Ok(212));
+        }
+
+        #[test]
+        fn get_cloned() {
+            let times = Arc::new(AtomicUsize::new(0));
+            let memo = {
+                let times = times.clone();
+                ThreadsafeMemo::new_arc(move || {
+                    times.fetch_add(1, Ordering::Release);
+                    String::from("memo")
+                })
+            };
+            let threads: Vec<_> = (0..8).map(|_| {
+                let memo = memo.clone();
+                thread::spawn(move || memo.get_cloned().unwrap())
+            }).collect();
+            for thread in threads {
+                assert_eq!(thread.join().unwrap(), "memo");
+            }
+            assert_eq!(times.load(Ordering::Acquire), 1);
+        }
+
+        #[test]
+        fn try_get_cloned() {
+            let memo = ThreadsafeMemo::new_arc(|| String::from("memo"));
+            assert_eq!(memo.try_get_cloned(), Ok(None));
+            let threads: Vec<_> = (0..8).map(|_| {
+                let memo = memo.clone();
+                thread::spawn(move || {
+                    let cloned = memo.get_cloned().unwrap();
+                    assert_eq!(memo.try_get_cloned(), Ok(Some(cloned)));
+                })
+            }).collect();
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            assert_eq!(memo.try_get_cloned(), Ok(Some(String::from("memo"))));
+        }
+    }
+
+    mod try_get_or_skip {
+        use super::super::{ThreadsafeMemo, TryGetResult};
+        use std::sync::mpsc::channel;
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+
+        #[test]
+        fn uncalculated() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            assert_eq!(memo.try_get_or_skip(), TryGetResult::Computed(&212));
+            assert_eq!(memo.try_get_or_skip(), TryGetResult::Computed(&212));
+        }
+
+        #[test]
+        fn poisoned() {
+            let memo: ThreadsafeMemo<u32, _> = ThreadsafeMemo::new(|| panic!());
+            assert!(thread::scope(|s| s.spawn(|| memo.try_get_or_skip()).join()).is_err());
+            assert_eq!(memo.try_get_or_skip(), TryGetResult::Poisoned);
+        }
+
+        #[test]
+        fn poll() {
+            let (started_tx, started_rx) = channel();
+            let (release_tx, release_rx) = channel::<()>();
+            let release_rx = Mutex::new(release_rx);
+            let memo = Arc::new(ThreadsafeMemo::new(move || {
+                started_tx.send(()).unwrap();
+                release_rx.lock().unwrap().recv().unwrap();
+                212
+            }));
+            let computer = {
+                let memo = memo.clone();
+                thread::spawn(move || assert_eq!(memo.get(), Ok(&212)))
+            };
+            started_rx.recv().unwrap();
+            let poller = {
+                let memo = memo.clone();
+                thread::spawn(move || {
+                    let mut skipped = 0;
+                    loop {
+                        match memo.try_get_or_skip() {
+                            TryGetResult::Computed(&value) => return (value, skipped),
+                            TryGetResult::InProgress => skipped += 1,
+                            TryGetResult::Poisoned => panic!("memo was poisoned"),
+                        }
+                        if skipped == 1 {
+                            release_tx.send(()).unwrap();
+                        }
+                        thread::yield_now();
+                    }
+                })
+            };
+            let (value, skipped) = poller.join().unwrap();
+            computer.join().unwrap();
+            assert_eq!(value, 212);
+            assert!(skipped >= 1);
+            assert_eq!(memo.get(), Ok(&212));
+        }
+    }
+
+    mod reset {
+        use super::super::ThreadsafeMemo;
+        use std::thread;
+
+        #[test]
+        fn calculated() {
+            let mut memo: ThreadsafeMemo<u32, fn() -> u32> = ThreadsafeMemo::new(|| 212);
+            assert_eq!(memo.get(), Ok(&212));
+            memo.reset(|| 666);
+            assert_eq!(memo.try_get(), Ok(None));
+            assert_eq!(memo.get(), Ok(&666));
+        }
+
+        #[test]
+        fn uncalculated() {
+            let mut memo: ThreadsafeMemo<u32, fn() -> u32> = ThreadsafeMemo::new(|| 212);
+            memo.reset(|| 666);
+            assert_eq!(memo.get(), Ok(&666));
+        }
+
+        #[test]
+        fn poisoned() {
+            fn fail() -> u32 { panic!() }
+            let mut memo: ThreadsafeMemo<u32, fn() -> u32> = ThreadsafeMemo::new(fail);
+            assert!(thread::scope(|s| s.spawn(|| memo.get()).join()).is_err());
+            memo.reset(|| 666);
+            assert!(!memo.is_poisoned());
+            assert_eq!(memo.get(), Ok(&666));
+        }
+    }
+
+    mod value {
+        use super::super::ThreadsafeMemo;
+        use std::sync::mpsc::channel;
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+
+        #[test]
+        fn states() {
+            let (started_tx, started_rx) = channel();
+            let (release_tx, release_rx) = channel::<()>();
+            let release_rx = Mutex::new(release_rx);
+            let memo = Arc::new(ThreadsafeMemo::new(move || {
+                started_tx.send(()).unwrap();
+                release_rx.lock().unwrap().recv().unwrap();
+                212
+            }));
+            assert_eq!(memo.value(), None);
+            let computer = {
+                let memo = memo.clone();
+                thread::spawn(move || memo.force())
+            };
+            started_rx.recv().unwrap();
+            assert_eq!(memo.value(), None);
+            release_tx.send(()).unwrap();
+            computer.join().unwrap().unwrap();
+            assert_eq!(memo.value(), Some(&212));
+        }
+
+        #[test]
+        fn poisoned() {
+            let memo: ThreadsafeMemo<u32, _> = ThreadsafeMemo::new(|| panic!());
+            assert!(thread::scope(|s| s.spawn(|| memo.get()).join()).is_err());
+            assert_eq!(memo.value(), None);
+        }
+    }
+
+    mod try_get_with {
+        use super::super::ThreadsafeMemo;
+        use memo::PoisonError;
+        use std::sync::atomic::Ordering;
+        use std::sync::Arc;
+        use std::thread;
+
+        #[test]
+        fn acquire() {
+            let memo = Arc::new(ThreadsafeMemo::new(|| 212));
+            assert_eq!(unsafe { memo.try_get_with(Ordering::Acquire) }, Ok(None));
+            let other = memo.clone();
+            let reader = thread::spawn(move || {
+                while unsafe { other.try_get_with(Ordering::Acquire) } == Ok(None) {
+                    thread::yield_now();
+                }
+                assert_eq!(unsafe { other.try_get_with(Ordering::SeqCst) }, Ok(Some(&212)));
+            });
+            memo.get().unwrap();
+            reader.join().unwrap();
+        }
+
+        // A relaxed load is only enough once something else, here the join,
+        // has made the calculating thread's writes visible.
+        #[test]
+        fn relaxed_after_join() {
+            let memo = Arc::new(ThreadsafeMemo::new(|| 212));
+            let other = memo.clone();
+            thread::spawn(move || other.force()).join().unwrap().unwrap();
+            assert_eq!(unsafe { memo.try_get_with(Ordering::Relaxed) }, Ok(Some(&212)));
+        }
+
+        #[test]
+        fn poisoned() {
+            let memo: ThreadsafeMemo<u32, _> = ThreadsafeMemo::new(|| panic!());
+            assert!(thread::scope(|s| s.spawn(|| memo.get()).join()).is_err());
+            assert_eq!(unsafe { memo.try_get_with(Ordering::Acquire) }, Err(PoisonError));
+        }
+
+        #[test]
+        #[should_panic(expected = "not a load ordering")]
+        #[cfg(debug_assertions)]
+        fn release() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            let _ = unsafe { memo.try_get_with(Ordering::Release) };
+        }
+    }
+
+    mod get_unchecked {
+        use super::super::ThreadsafeMemo;
+        use std::sync::Arc;
+        use std::thread;
+
+        #[test]
+        fn after_get() {
+            let memo = Arc::new(ThreadsafeMemo::new(|| 212));
+            assert_eq!(memo.get(), Ok(&212));
+            let other = memo.clone();
+            let value = thread::spawn(move || unsafe { *other.get_unchecked() }).join().unwrap();
+            assert_eq!(value, 212);
+            assert_eq!(unsafe { memo.get_unchecked() }, &212);
+        }
+
+        #[test]
+        #[should_panic]
+        #[cfg(debug_assertions)]
+        fn uncalculated() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            unsafe { memo.get_unchecked(); }
+        }
+    }
+
+    mod try_get_relaxed {
+        use super::super::ThreadsafeMemo;
+        use memo::PoisonError;
+        use std::sync::Arc;
+        use std::thread;
+        use std::panic;
+
+        #[test]
+        #[allow(unused_must_use)]
+        fn states() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            assert_eq!(memo.try_get_relaxed(), Ok(None));
+            memo.get().unwrap();
+            assert_eq!(memo.try_get_relaxed(), Ok(Some(&212)));
+
+            let memo = ThreadsafeMemo::new(|| -> u32 { panic!() });
+            panic::catch_unwind(|| {
+                memo.get();
+            }).unwrap_err();
+            assert_eq!(memo.try_get_relaxed(), Err(PoisonError));
+        }
+
+        // Readers never take part in the calculation, so the only edge that can
+        // publish the value to them is the release swap in `Finish` paired with
+        // the acquire fence. A reader that sees CALCULATED must therefore see the
+        // complete vector, never a partially written or missing one.
+        #[test]
+        fn publish() {
+            for _ in 0..32 {
+                let memo = Arc::new(ThreadsafeMemo::new(|| (0..64).collect::<Vec<u32>>()));
+                let readers: Vec<_> = (0..4).map(|_| {
+                    let memo = memo.clone();
+                    thread::spawn(move || {
+                        loop {
+                            if let Some(value) = memo.try_get_relaxed().unwrap() {
+                                assert_eq!(value.len(), 64);
+                                assert_eq!(value[63], 63);
+                                break;
+                            }
+                            thread::yield_now();
+                        }
+                    })
+                }).collect();
+                memo.get().unwrap();
+                for reader in readers {
+                    reader.join().unwrap();
+                }
+            }
+        }
+    }
+
+    mod force {
+        use super::super::ThreadsafeMemo;
+        use memo::PoisonError;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::thread;
+        use std::panic;
+
+        #[test]
+        fn stampede() {
+            let times = Arc::new(AtomicUsize::new(0));
+            let memo = {
+                let times = times.clone();
+                Arc::new(ThreadsafeMemo::new(move || {
+                    for _ in 0..3 {
+                        thread::yield_now();
+                    }
+                    times.fetch_add(1, Ordering::Release);
+                    212
+                }))
+            };
+            let threads: Vec<_> = (0..12).map(|_| {
+                let memo = memo.clone();
+                thread::spawn(move || {
+                    memo.force().unwrap();
+                })
+            }).collect();
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            assert_eq!(times.load(Ordering::Acquire), 1);
+            assert_eq!(memo.try_get(), Ok(Some(&212)));
+        }
+
+        #[test]
+        #[allow(unused_must_use)]
+        fn poison() {
+            let memo = ThreadsafeMemo::new(|| -> u32 { panic!() });
+            panic::catch_unwind(|| {
+                memo.force();
+            }).unwrap_err();
+            assert_eq!(memo.force(), Err(PoisonError));
+        }
+    }
+
+    mod send {
+        use super::super::ThreadsafeMemo;
+        use std::thread;
+
+        #[test]
+        fn move_uncalculated() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            let value = thread::spawn(move || *memo.get().unwrap()).join().unwrap();
+            assert_eq!(value, 212);
+        }
+
+        #[test]
+        fn move_calculated() {
+            let memo = ThreadsafeMemo::new(|| String::from("memo"));
+            memo.get().unwrap();
+            let value = thread::spawn(move || memo.take().unwrap()).join().unwrap();
+            assert_eq!(value, "memo");
+        }
+    }
+
+    mod state {
+        use super::super::{ThreadsafeMemo, MemoState};
+        use std::sync::mpsc::channel;
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+        use std::panic;
+
+        #[test]
+        fn calculated() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            assert_eq!(memo.state(), MemoState::Uncalculated);
+            assert!(!memo.is_poisoned());
+            memo.get().unwrap();
+            assert_eq!(memo.state(), MemoState::Calculated);
+            assert!(!memo.is_poisoned());
+
+            let memo: ThreadsafeMemo<u32, fn() -> u32> = ThreadsafeMemo::with_value(212);
+            assert_eq!(memo.state(), MemoState::Calculated);
+        }
+
+        #[test]
+        #[allow(unused_must_use)]
+        fn poisoned() {
+            fn fail() -> u32 { panic!() }
+            fn succeed() -> u32 { 212 }
+            let memo = ThreadsafeMemo::new(fail as fn() -> u32);
+            panic::catch_unwind(|| {
+                memo.get();
+            }).unwrap_err();
+            assert_eq!(memo.state(), MemoState::Poisoned);
+            assert!(memo.is_poisoned());
+            assert!(memo.unpoison(succeed));
+            assert_eq!(memo.state(), MemoState::Uncalculated);
+            assert!(!memo.is_poisoned());
+        }
+
+        #[test]
+        fn working() {
+            let (started_tx, started_rx) = channel();
+            let (release_tx, release_rx) = channel::<()>();
+            let started_tx = Mutex::new(started_tx);
+            let release_rx = Mutex::new(release_rx);
+            let memo = Arc::new(ThreadsafeMemo::new(move || {
+                started_tx.lock().unwrap().send(()).unwrap();
+                release_rx.lock().unwrap().recv().unwrap();
+                212
+            }));
+            let calculator = {
+                let memo = memo.clone();
+                thread::spawn(move || {
+                    memo.get().unwrap();
+                })
+            };
+            started_rx.recv().unwrap();
+            assert_eq!(memo.state(), MemoState::Working);
+            assert!(!memo.is_poisoned());
+            release_tx.send(()).unwrap();
+            calculator.join().unwrap();
+            assert_eq!(memo.state(), MemoState::Calculated);
+        }
+    }
+
+    mod alignment {
+        use super::super::{ThreadsafeMemo, SpinState, STATE_MASK};
+        use blocker::Blocker;
+        use std::mem;
+        use std::sync::mpsc::channel;
+        use std::sync::{Arc, Barrier, Mutex};
+        use std::thread;
+
+        // A byte-aligned blocker, so `SpinState` gets no alignment from it.
+        #[derive(Clone)]
+        struct Yield;
+
+        impl Blocker for Yield {
+            fn current() -> Yield {
+                Yield
+            }
+
+            fn block(&self) {
+                thread::yield_now();
+            }
+
+            fn unblock(&self) {  }
+        }
+
+        #[test]
+        fn spin_state() {
+            assert!(mem::align_of::<SpinState<Yield>>() > STATE_MASK);
+            let nodes = [
+                (0u8, SpinState::new(1, Yield)),
+                (0u8, SpinState::new(1, Yield)),
+                (0u8, SpinState::new(1, Yield)),
+            ];
+            for (_, node) in &nodes {
+                assert_eq!(node as *const SpinState<Yield> as usize & STATE_MASK, 0);
+            }
+        }
+
+        #[test]
+        fn byte_aligned_blocker() {
+            const THREADS: usize = 8;
+            let (release_tx, release_rx) = channel::<()>();
+            let release_rx = Mutex::new(release_rx);
+            let memo: Arc<ThreadsafeMemo<u32, _, Yield>> = Arc::new(ThreadsafeMemo::with_blocker(move || {
+                release_rx.lock().unwrap().recv().unwrap();
+                212
+            }));
+            let barrier = Arc::new(Barrier::new(THREADS + 1));
+            let threads: Vec<_> = (0..THREADS).map(|_| {
+                let memo = memo.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    barrier.wait();
+                    *memo.get().unwrap()
+                })
+            }).collect();
+            barrier.wait();
+            release_tx.send(()).unwrap();
+            for thread in threads {
+                assert_eq!(thread.join().unwrap(), 212);
+            }
+        }
+    }
+
+    mod fifo {
+        use super::super::{ThreadsafeMemo, SpinState, STATE_MASK};
+        use blocker::Blocker;
+        use std::cell::RefCell;
+        use std::sync::atomic::Ordering;
+        use std::sync::{Arc, Mutex};
+        use std::thread::{self, Thread};
+
+        pub(super) type Order = Arc<Mutex<Vec<usize>>>;
+        pub(super) type RecordingMemo = ThreadsafeMemo<u32, fn() -> u32, Recording>;
+
+        thread_local! {
+            pub(super) static WAITER: RefCell<Option<(usize, Order)>> = const { RefCell::new(None) };
+        }
+
+        // Records the order in which waiters are woken; `priority` uses it too.
+        #[derive(Clone)]
+        pub(super) struct Recording {
+            thread: Thread,
+            waiter: Option<(usize, Order)>,
+        }
+
+        impl Blocker for Recording {
+            fn current() -> Recording {
+                Recording {
+                    thread: thread::current(),
+                    waiter: WAITER.with(|waiter| waiter.borrow().clone()),
+                }
+            }
+
+            fn block(&self) {
+                thread::park();
+            }
+
+            fn unblock(&self) {
+                if let Some((id, ref order)) = self.waiter {
+                    order.lock().unwrap().push(id);
+                }
+                self.thread.unpark();
+            }
+        }
+
+        pub(super) fn queued(memo: &RecordingMemo) -> usize {
+            let mut head = (memo.state.load(Ordering::Acquire) & !STATE_MASK) as *const SpinState<Recording>;
+            let mut queued = 0;
+            while !head.is_null() {
+                queued += 1;
+                head = unsafe { (*head).next };
+            }
+            queued
+        }
+
+        #[test]
+        fn wakeup_order() {
+            let memo: Arc<RecordingMemo> = Arc::new(ThreadsafeMemo::with_blocker(|| 212));
+            let order = Order::default();
+            let guard = memo.try_begin_compute().unwrap();
+            let waiters: Vec<_> = (0..6).map(|id| {
+                let waiter = {
+                    let memo = memo.clone();
+                    let order = order.clone();
+                    thread::spawn(move || {
+                        WAITER.with(|waiter| *waiter.borrow_mut() = Some((id, order)));
+                        assert_eq!(memo.get(), Ok(&212));
+                    })
+                };
+                // Enqueue one waiter at a time so that arrival order is known.
+                while queued(&memo) != id + 1 {
+                    thread::yield_now();
+                }
+                waiter
+            }).collect();
+            guard.set(212);
+            assert_eq!(*order.lock().unwrap(), [0, 1, 2, 3, 4, 5]);
+            for waiter in waiters {
+                waiter.join().unwrap();
+            }
+        }
+    }
+
+    mod blocker {
+        use super::super::{ThreadsafeMemo, MemoState};
+        use blocker::Blocker;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::thread;
+        use std::time::Duration;
+
+        #[derive(Clone)]
+        struct Spin;
+
+        impl Blocker for Spin {
+            fn current() -> Spin {
+                Spin
+            }
+
+            fn block(&self) {
+                thread::yield_now();
+            }
+
+            fn unblock(&self) {  }
+        }
+
+        #[test]
+        fn spin_stampede() {
+            let times = Arc::new(AtomicUsize::new(0));
+            let memo: Arc<ThreadsafeMemo<_, _, Spin>> = {
+                let times = times.clone();
+                Arc::new(ThreadsafeMemo::with_blocker(move || {
+                    thread::sleep(Duration::from_millis(10));
+                    times.fetch_add(1, Ordering::Release);
+                    212
+                }))
+            };
+            let threads: Vec<_> = (0..12).map(|_| {
+                let memo = memo.clone();
+                thread::spawn(move || {
+                    assert_eq!(*memo.get().unwrap(), 212);
+                })
+            }).collect();
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            assert_eq!(times.load(Ordering::Acquire), 1);
+        }
+
+        #[test]
+        fn spin_timeout() {
+            let memo: Arc<ThreadsafeMemo<_, _, Spin>> = Arc::new(ThreadsafeMemo::with_blocker(|| {
+                thread::sleep(Duration::from_millis(200));
+                212
+            }));
+            let calculator = {
+                let memo = memo.clone();
+                thread::spawn(move || {
+                    memo.get().unwrap();
+                })
+            };
+            while memo.state() != MemoState::Working {
+                thread::yield_now();
+            }
+            assert_eq!(memo.get_timeout(Duration::from_millis(10)), Ok(None));
+            calculator.join().unwrap();
+            assert_eq!(memo.get_timeout(Duration::from_millis(10)), Ok(Some(&212)));
+        }
+    }
+
+    mod convert {
+        use super::super::ThreadsafeMemo;
+        use memo::{Memo, PoisonError};
+        use aliasable_memo::AliasableMemo;
+        use std::convert::TryFrom;
+        use std::panic;
+
+        #[test]
+        fn from_memo() {
+            let memo = ThreadsafeMemo::from(Memo::new(|| 212));
+            assert_eq!(memo.try_get(), Ok(None));
+            assert_eq!(memo.get(), Ok(&212));
+
+            let mut memo = Memo::new(|| 212);
+            memo.get();
+            let memo = ThreadsafeMemo::from(memo);
+            assert_eq!(memo.try_get(), Ok(Some(&212)));
+        }
+
+        #[test]
+        fn from_poisoned_memo() {
+            let mut memo = Memo::new(|| -> u32 { panic!() });
+            panic::catch_unwind(panic::AssertUnwindSafe(|| *memo.get())).unwrap_err();
+            assert!(memo.is_poisoned());
+            let memo = ThreadsafeMemo::from(memo);
+            assert!(memo.is_poisoned());
+            assert_eq!(memo.get(), Err(PoisonError));
+        }
+
+        #[test]
+        fn from_empty_memo() {
+            let mut memo = Memo::new(|| 212);
+            assert!(memo.take_func().is_some());
+            let memo = ThreadsafeMemo::from(memo);
+            assert!(memo.is_poisoned());
+            assert_eq!(memo.get(), Err(PoisonError));
+        }
+
+        #[test]
+        fn from_aliasable_memo() {
+            let memo = ThreadsafeMemo::from(AliasableMemo::new(|| 212));
+            assert_eq!(memo.try_get(), Ok(None));
+            assert_eq!(memo.get(), Ok(&212));
+
+            let memo = AliasableMemo::new(|| 212);
+            memo.get();
+            let memo = ThreadsafeMemo::from(memo);
+            assert_eq!(memo.try_get(), Ok(Some(&212)));
+        }
+
+        #[test]
+        fn into_memo() {
+            let mut memo = ThreadsafeMemo::new(|| 212).into_memo().unwrap();
+            assert!(memo.is_uncalculated());
+            assert_eq!(*memo.get(), 212);
+
+            let memo = ThreadsafeMemo::new(|| 212);
+            memo.get().unwrap();
+            let memo = memo.into_memo().unwrap();
+            assert_eq!(memo.try_get(), Some(&212));
+        }
+
+        #[test]
+        fn try_from() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            memo.get().unwrap();
+            let memo: Memo<u32, _> = Memo::try_from(memo).unwrap();
+            assert_eq!(memo.try_get(), Some(&212));
+        }
+
+        #[test]
+        #[allow(unused_must_use)]
+        fn into_memo_poisoned() {
+            let memo = ThreadsafeMemo::new(|| -> u32 { panic!() });
+            panic::catch_unwind(|| {
+                memo.get();
+            }).unwrap_err();
+            assert!(memo.into_memo().is_err());
+        }
+    }
+
+    mod spin_limit {
+        use super::super::{ThreadsafeMemo, WaitResult};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::mpsc::channel;
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+        use std::time::{Duration, Instant};
+
+        fn stampede(limit: usize, work: Duration) -> Duration {
+            let times = Arc::new(AtomicUsize::new(0));
+            let memo = {
+                let times = times.clone();
+                Arc::new(ThreadsafeMemo::with_spin_limit(move || {
+                    thread::sleep(work);
+                    times.fetch_add(1, Ordering::Release);
+                    212
+                }, limit))
+            };
+            let start = Instant::now();
+            let threads: Vec<_> = (0..12).map(|_| {
+                let memo = memo.clone();
+                thread::spawn(move || {
+                    assert_eq!(*memo.get().unwrap(), 212);
+                })
+            }).collect();
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            assert_eq!(times.load(Ordering::Acquire), 1);
+            start.elapsed()
+        }
+
+        #[test]
+        fn spin_only() {
+            for _ in 0..8 {
+                stampede(usize::MAX, Duration::from_millis(1));
+            }
+        }
+
+        #[test]
+        fn spin_then_park() {
+            for _ in 0..8 {
+                stampede(16, Duration::from_millis(5));
+            }
+        }
+
+        #[test]
+        fn timeout() {
+            let (release_tx, release_rx) = channel::<()>();
+            let release_rx = Mutex::new(release_rx);
+            let memo = ThreadsafeMemo::with_spin_limit(move || {
+                release_rx.lock().unwrap().recv().unwrap();
+                212
+            }, usize::MAX);
+            thread::scope(|s| {
+                let guard = memo.try_begin_compute().unwrap();
+                let waiter = s.spawn(|| {
+                    let start = Instant::now();
+                    assert_eq!(memo.get_timeout(Duration::from_millis(20)), Ok(None));
+                    assert_eq!(memo.wait_timeout(Duration::from_millis(20)), Ok(WaitResult::TimedOut));
+                    start.elapsed()
+                });
+                let elapsed = waiter.join().unwrap();
+                assert!(elapsed >= Duration::from_millis(40));
+                release_tx.send(()).unwrap();
+                assert_eq!(*guard.compute(), 212);
+            });
+        }
+    }
+
+    mod new_try {
+        use super::super::{ThreadsafeMemo, MemoState, TryError};
+        use memo::PoisonError;
+        use std::panic::{self, AssertUnwindSafe};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::mpsc::channel;
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+
+        #[test]
+        fn ok() {
+            let memo = ThreadsafeMemo::new_try(|| -> Result<u32, ()> { Ok(212) });
+            assert_eq!(memo.get_try(), Ok(&212));
+            assert_eq!(memo.get_try(), Ok(&212));
+            assert_eq!(memo.get(), Ok(&Ok(212)));
+        }
+
+        #[test]
+        fn err() {
+            let memo = ThreadsafeMemo::new_try(|| -> Result<u32, &str> { Err("failed") });
+            assert_eq!(memo.get_try(), Err(TryError::Failed("failed")));
+            assert!(memo.is_poisoned());
+            assert_eq!(memo.get_try(), Err(TryError::Failed("failed")));
+            assert_eq!(memo.get(), Err(PoisonError));
+        }
+
+        #[test]
+        fn panic() {
+            let memo = ThreadsafeMemo::new_try(|| -> Result<u32, ()> { panic!() });
+            assert!(thread::scope(|s| s.spawn(|| memo.get_try()).join()).is_err());
+            assert_eq!(memo.get_try(), Err(TryError::Poisoned));
+        }
+
+        #[test]
+        fn unpoison() {
+            fn fail() -> Result<u32, &'static str> { Err("failed") }
+            fn succeed() -> Result<u32, &'static str> { Ok(212) }
+            let memo: ThreadsafeMemo<_, fn() -> Result<u32, &'static str>> = ThreadsafeMemo::new_try(fail);
+            assert_eq!(memo.get_try(), Err(TryError::Failed("failed")));
+            assert!(memo.unpoison(succeed));
+            assert_eq!(memo.get_try(), Ok(&212));
+        }
+
+        // The clone that panics runs under the readers' lock, which mustn't
+        // keep the memo from being unpoisoned afterwards.
+        #[test]
+        fn panicking_clone() {
+            #[derive(Debug, PartialEq)]
+            struct Fragile;
+
+            impl Clone for Fragile {
+                fn clone(&self) -> Fragile {
+                    panic!()
+                }
+            }
+
+            fn fail() -> Result<u32, Fragile> { Err(Fragile) }
+            fn succeed() -> Result<u32, Fragile> { Ok(212) }
+            let memo: ThreadsafeMemo<_, fn() -> Result<u32, Fragile>> = ThreadsafeMemo::new_try(fail);
+            for _ in 0..2 {
+                assert!(panic::catch_unwind(AssertUnwindSafe(|| memo.get_try().is_ok())).is_err());
+                assert!(memo.is_poisoned());
+            }
+            assert!(memo.unpoison(succeed));
+            assert_eq!(memo.get_try(), Ok(&212));
+        }
+
+        #[test]
+        fn waiters() {
+            let times = Arc::new(AtomicUsize::new(0));
+            let (started_tx, started_rx) = channel();
+            let (release_tx, release_rx) = channel::<()>();
+            let release_rx = Mutex::new(release_rx);
+            let memo = {
+                let times = times.clone();
+                Arc::new(ThreadsafeMemo::new_try(move || -> Result<u32, String> {
+                    times.fetch_add(1, Ordering::SeqCst);
+                    started_tx.send(()).unwrap();
+                    release_rx.lock().unwrap().recv().unwrap();
+                    Err(String::from("failed"))
+                }))
+            };
+            let computer = {
+                let memo = memo.clone();
+                thread::spawn(move || memo.get_try().map(|_| ()))
+            };
+            started_rx.recv().unwrap();
+            let waiters: Vec<_> = (0..8).map(|_| {
+                let memo = memo.clone();
+                thread::spawn(move || memo.get_try().map(|_| ()))
+            }).collect();
+            release_tx.send(()).unwrap();
+            assert_eq!(computer.join().unwrap(), Err(TryError::Failed(String::from("failed"))));
+            for waiter in waiters {
+                assert_eq!(waiter.join().unwrap(), Err(TryError::Failed(String::from("failed"))));
+            }
+            assert_eq!(times.load(Ordering::SeqCst), 1);
+        }
+
+        // Reading the error must not claim the memo, or the readers would
+        // show up as a calculation in progress.
+        #[test]
+        fn readers_stay_poisoned() {
+            let memo = ThreadsafeMemo::new_try(|| -> Result<u32, String> { Err(String::from("failed")) });
+            assert!(memo.get_try().is_err());
+            thread::scope(|s| {
+                for _ in 0..4 {
+                    s.spawn(|| {
+                        for _ in 0..1000 {
+                            assert_eq!(memo.get_try(), Err(TryError::Failed(String::from("failed"))));
+                        }
+                    });
+                }
+                for _ in 0..1000 {
+                    assert_eq!(memo.state(), MemoState::Poisoned);
+                }
+            });
+        }
+
+        #[test]
+        fn unpoison_while_reading() {
+            fn fail() -> Result<u32, String> { Err(String::from("failed")) }
+            fn succeed() -> Result<u32, String> { Ok(212) }
+            let memo: ThreadsafeMemo<_, fn() -> Result<u32, String>> = ThreadsafeMemo::new_try(fail);
+            assert!(memo.get_try().is_err());
+            thread::scope(|s| {
+                let readers: Vec<_> = (0..4).map(|_| s.spawn(|| {
+                    loop {
+                        match memo.get_try() {
+                            Ok(value) => return *value,
+                            Err(err) => assert_eq!(err, TryError::Failed(String::from("failed"))),
+                        }
+                    }
+                })).collect();
+                assert!(memo.unpoison(succeed));
+                for reader in readers {
+                    assert_eq!(reader.join().unwrap(), 212);
+                }
+            });
+        }
+    }
+
+    mod map {
+        use super::super::ThreadsafeMemo;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::thread;
+
+        #[test]
+        fn concurrent() {
+            let sources = Arc::new(AtomicUsize::new(0));
+            let maps = Arc::new(AtomicUsize::new(0));
+            let source = {
+                let sources = sources.clone();
+                ThreadsafeMemo::new(move || {
+                    sources.fetch_add(1, Ordering::SeqCst);
+                    212
+                })
+            };
+            let mapped = {
+                let maps = maps.clone();
+                Arc::new(source.map(move |&value| {
+                    maps.fetch_add(1, Ordering::SeqCst);
+                    value * 2
+                }))
+            };
+            let threads: Vec<_> = (0..8).map(|_| {
+                let mapped = mapped.clone();
+                thread::spawn(move || {
+                    assert_eq!(*mapped.get().unwrap(), 424);
+                })
+            }).collect();
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            assert_eq!(sources.load(Ordering::SeqCst), 1);
+            assert_eq!(maps.load(Ordering::SeqCst), 1);
+        }
+
+        #[test]
+        fn calculated_source() {
+            let source = ThreadsafeMemo::new(|| 212);
+            assert_eq!(source.get(), Ok(&212));
+            let mapped = source.map(|value| value.to_string());
+            assert_eq!(mapped.get().map(String::as_str), Ok("212"));
+        }
+
+        #[test]
+        fn poisoned_source() {
+            let source: ThreadsafeMemo<u32, _> = ThreadsafeMemo::new(|| panic!());
+            assert!(thread::scope(|s| s.spawn(|| source.get()).join()).is_err());
+            let mapped = source.map(|&value| value + 1);
+            assert!(thread::scope(|s| s.spawn(|| mapped.get()).join()).is_err());
+            assert!(mapped.is_poisoned());
+        }
+    }
+
+    mod get_arc {
+        use super::super::ThreadsafeMemo;
+        use memo::PoisonError;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::thread;
+
+        #[test]
+        fn shared_allocation() {
+            let times = Arc::new(AtomicUsize::new(0));
+            let memo = {
+                let times = times.clone();
+                ThreadsafeMemo::new_arc(move || {
+                    times.fetch_add(1, Ordering::SeqCst);
+                    Arc::new(vec![212; 64])
+                })
+            };
+            assert_eq!(memo.try_get_arc(), Ok(None));
+            let threads: Vec<_> = (0..8).map(|_| {
+                let memo = memo.clone();
+                thread::spawn(move || memo.get_arc().unwrap())
+            }).collect();
+            let arcs: Vec<_> = threads.into_iter().map(|thread| thread.join().unwrap()).collect();
+            let first = memo.try_get_arc().unwrap().unwrap();
+            assert!(arcs.iter().all(|arc| Arc::ptr_eq(arc, &first)));
+            assert_eq!(Arc::strong_count(&first), 10);
+            assert_eq!(times.load(Ordering::SeqCst), 1);
+        }
+
+        #[test]
+        fn poisoned() {
+            let memo: ThreadsafeMemo<Arc<u32>, _> = ThreadsafeMemo::new(|| panic!());
+            assert!(thread::scope(|s| s.spawn(|| memo.get_arc()).join()).is_err());
+            assert_eq!(memo.get_arc(), Err(PoisonError));
+            assert_eq!(memo.try_get_arc(), Err(PoisonError));
+        }
+    }
+
+    mod once_cell {
+        use super::super::ThreadsafeMemo;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::mpsc::channel;
+        use std::sync::{Arc, Barrier};
+        use std::thread;
+        use std::time::Duration;
+
+        #[test]
+        fn set() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            assert_eq!(memo.set(666), Ok(()));
+            assert_eq!(memo.set(777), Err(777));
+            assert_eq!(memo.get(), Ok(&666));
+        }
+
+        #[test]
+        fn set_poisoned() {
+            let memo: ThreadsafeMemo<u32, _> = ThreadsafeMemo::new(|| panic!());
+            assert!(thread::scope(|s| s.spawn(|| memo.get()).join()).is_err());
+            assert_eq!(memo.set(666), Err(666));
+        }
+
+        #[test]
+        fn set_while_working() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            thread::scope(|s| {
+                let (started_tx, started_rx) = channel();
+                let guard = memo.try_begin_compute().unwrap();
+                let memo = &memo;
+                let setter = s.spawn(move || {
+                    started_tx.send(()).unwrap();
+                    memo.set(666)
+                });
+                started_rx.recv().unwrap();
+                thread::sleep(Duration::from_millis(10));
+                assert_eq!(*guard.compute(), 212);
+                assert_eq!(setter.join().unwrap(), Err(666));
+            });
+        }
+
+        #[test]
+        fn set_after_abandoned() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            thread::scope(|s| {
+                let (started_tx, started_rx) = channel();
+                let guard = memo.try_begin_compute().unwrap();
+                let memo = &memo;
+                let setter = s.spawn(move || {
+                    started_tx.send(()).unwrap();
+                    memo.set(666)
+                });
+                started_rx.recv().unwrap();
+                thread::sleep(Duration::from_millis(10));
+                drop(guard);
+                assert_eq!(setter.join().unwrap(), Ok(()));
+            });
+            assert_eq!(memo.get(), Ok(&666));
+        }
+
+        #[test]
+        fn concurrent_set() {
+            let memo: Arc<ThreadsafeMemo<usize, fn() -> usize>> = Arc::new(ThreadsafeMemo::new(|| 212));
+            let barrier = Arc::new(Barrier::new(8));
+            let threads: Vec<_> = (0..8).map(|i| {
+                let memo = memo.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    barrier.wait();
+                    memo.set(i).is_ok()
+                })
+            }).collect();
+            let winners = threads.into_iter().map(|t| t.join().unwrap()).filter(|&won| won).count();
+            assert_eq!(winners, 1);
+            assert!(*memo.get().unwrap() < 8);
+        }
+
+        #[test]
+        fn get_or_init() {
+            let times = AtomicUsize::new(0);
+            let memo = ThreadsafeMemo::new(|| 212);
+            assert_eq!(memo.get_or_init(|| {
+                times.fetch_add(1, Ordering::SeqCst);
+                666
+            }), Ok(&666));
+            assert_eq!(memo.get_or_init(|| {
+                times.fetch_add(1, Ordering::SeqCst);
+                777
+            }), Ok(&666));
+            assert_eq!(memo.get(), Ok(&666));
+            assert_eq!(times.load(Ordering::SeqCst), 1);
+        }
+
+        #[test]
+        fn get_or_init_after_get() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            assert_eq!(memo.get(), Ok(&212));
+            assert_eq!(memo.get_or_init(|| 666), Ok(&212));
+        }
+    }
+
+    mod compute_guard {
+        use super::super::ThreadsafeMemo;
+        use std::sync::mpsc::channel;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::thread;
+
+        #[test]
+        fn dedicated() {
+            let times = Arc::new(AtomicUsize::new(0));
+            let memo = {
+                let times = times.clone();
+                Arc::new(ThreadsafeMemo::new(move || {
+                    times.fetch_add(1, Ordering::SeqCst);
+                    212
+                }))
+            };
+            let (claimed_tx, claimed_rx) = channel();
+            let (release_tx, release_rx) = channel::<()>();
+            let computer = {
+                let memo = memo.clone();
+                thread::Builder::new().name("computer".to_owned()).spawn(move || {
+                    let guard = memo.try_begin_compute().unwrap();
+                    claimed_tx.send(()).unwrap();
+                    release_rx.recv().unwrap();
+                    assert_eq!(*guard.compute(), 212);
+                }).unwrap()
+            };
+            claimed_rx.recv().unwrap();
+            assert!(memo.try_begin_compute().is_none());
+            let waiters: Vec<_> = (0..4).map(|_| {
+                let memo = memo.clone();
+                thread::spawn(move || {
+                    assert_eq!(*memo.get().unwrap(), 212);
+                })
+            }).collect();
+            release_tx.send(()).unwrap();
+            computer.join().unwrap();
+            for waiter in waiters {
+                waiter.join().unwrap();
+            }
+            assert_eq!(times.load(Ordering::SeqCst), 1);
+        }
+
+        #[test]
+        fn set() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            assert_eq!(*memo.try_begin_compute().unwrap().set(666), 666);
+            assert_eq!(memo.get(), Ok(&666));
+            assert!(memo.try_begin_compute().is_none());
+        }
+
+        #[test]
+        fn abandon() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            drop(memo.try_begin_compute().unwrap());
+            assert_eq!(memo.try_get(), Ok(None));
+            assert_eq!(memo.get(), Ok(&212));
+        }
+    }
+
+    mod wait {
+        use super::super::{ThreadsafeMemo, WaitResult};
+        use memo::PoisonError;
+        use std::sync::mpsc::channel;
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+        use std::time::Duration;
+        use std::panic;
+
+        #[test]
+        fn producer() {
+            let (started_tx, started_rx) = channel();
+            let (release_tx, release_rx) = channel::<()>();
+            let started_tx = Mutex::new(started_tx);
+            let release_rx = Mutex::new(release_rx);
+            let memo = Arc::new(ThreadsafeMemo::new(move || {
+                started_tx.lock().unwrap().send(()).unwrap();
+                release_rx.lock().unwrap().recv().unwrap();
+                212
+            }));
+            assert_eq!(memo.wait(), Ok(WaitResult::Uncalculated));
+            assert_eq!(memo.wait_timeout(Duration::from_millis(10)), Ok(WaitResult::Uncalculated));
+            assert_eq!(memo.try_get(), Ok(None));
+            let producer = {
+                let memo = memo.clone();
+                thread::spawn(move || {
+                    memo.force().unwrap();
+                })
+            };
+            started_rx.recv().unwrap();
+            let consumer = {
+                let memo = memo.clone();
+                thread::spawn(move || {
+                    assert_eq!(memo.wait(), Ok(WaitResult::Ready));
+                    assert_eq!(memo.try_get(), Ok(Some(&212)));
+                })
+            };
+            assert_eq!(memo.wait_timeout(Duration::from_millis(10)), Ok(WaitResult::TimedOut));
+            release_tx.send(()).unwrap();
+            producer.join().unwrap();
+            consumer.join().unwrap();
+            assert_eq!(memo.wait(), Ok(WaitResult::Ready));
+        }
+
+        #[test]
+        #[allow(unused_must_use)]
+        fn poison() {
+            let memo = ThreadsafeMemo::new(|| -> u32 { panic!() });
+            panic::catch_unwind(|| {
+                memo.get();
+            }).unwrap_err();
+            assert_eq!(memo.wait(), Err(PoisonError));
+            assert_eq!(memo.wait_timeout(Duration::from_millis(10)), Err(PoisonError));
+        }
+    }
+
+    mod take {
+        use super::super::{ThreadsafeMemo, WORKING};
+        use memo::PoisonError;
+        use std::sync::atomic::Ordering;
+        use std::sync::mpsc::channel;
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+        use std::time::Duration;
+
+        #[test]
+        fn empty() {
+            let memo: ThreadsafeMemo<u32, fn() -> u32> = ThreadsafeMemo::empty();
+            assert_eq!(memo.try_take(), Ok(None));
+        }
+
+        #[test]
+        #[should_panic(expected = "missing its closure")]
+        fn empty_take() {
+            let memo: ThreadsafeMemo<u32, fn() -> u32> = ThreadsafeMemo::empty();
+            let _ = memo.take();
+        }
+
+        #[test]
+        #[should_panic(expected = "still working")]
+        fn working() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            memo.state.store(WORKING | 4, Ordering::Relaxed);
+            let _ = memo.try_take();
+        }
+
+        #[test]
+        fn take_or_compute() {
+            let memo = Arc::new(ThreadsafeMemo::new(|| 212));
+            let other = memo.clone();
+            thread::spawn(move || drop(other)).join().unwrap();
+            let memo = Arc::try_unwrap(memo).ok().unwrap();
+            assert_eq!(memo.take_or_compute(), Ok(212));
+
+            let memo = Arc::new(ThreadsafeMemo::new(|| 212));
+            memo.get().unwrap();
+            let memo = Arc::try_unwrap(memo).ok().unwrap();
+            assert_eq!(memo.take_or_compute(), Ok(212));
+        }
+
+        struct Counted(Arc<Mutex<Vec<&'static str>>>, &'static str);
+
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.0.lock().unwrap().push(self.1);
+            }
         }
-    }
 
-    pub fn try_take(self) -> Result<Option<T>, ()> {
-        match (atomic_usize_into_inner(self.state), unsafe { self.core.into_inner() }) {
-            (POISONED, _) => Err(()),
-            (UNCALCULATED, _) => Ok(None),
-            (CALCULATED, ThreadsafeMemoCore { func: None, value: Some(value) }) => Ok(Some(value)),
-            _ => panic!("ThreadsafeMemo had an invalid state!")
+        #[test]
+        fn drop_uncalculated() {
+            let drops = Arc::new(Mutex::new(vec![]));
+            let memo = {
+                let captured = Counted(drops.clone(), "captured");
+                let drops = drops.clone();
+                ThreadsafeMemo::new(move || {
+                    let _captured = captured;
+                    Counted(drops, "value")
+                })
+            };
+            let value = memo.take().unwrap();
+            assert_eq!(*drops.lock().unwrap(), ["captured"]);
+            drop(value);
+            assert_eq!(*drops.lock().unwrap(), ["captured", "value"]);
         }
-    }
 
-    pub fn unpoison(&self, func: F) -> bool {
-        match self.state.compare_exchange(POISONED,
-                                          WORKING,
-                                          Ordering::AcqRel,
-                                          Ordering::Acquire) {
-            Ok(_) => {
-                let mut finish = Finish {
-                    destination_state: POISONED,
-                    state: &self.state,
-                };
-                unsafe {
-                    *self.core.get() = ThreadsafeMemoCore {
-                        func: Some(func),
-                        value: None,
-                    };
-                }
-                finish.destination_state = UNCALCULATED;
-                true
-            },
-            Err(_) => false,
+        #[test]
+        fn drop_calculated() {
+            let drops = Arc::new(Mutex::new(vec![]));
+            let memo = {
+                let captured = Counted(drops.clone(), "captured");
+                let drops = drops.clone();
+                ThreadsafeMemo::new(move || {
+                    let _captured = captured;
+                    Counted(drops, "value")
+                })
+            };
+            memo.get().unwrap();
+            assert_eq!(*drops.lock().unwrap(), ["captured"]);
+            let value = memo.take().unwrap();
+            assert_eq!(*drops.lock().unwrap(), ["captured"]);
+            drop(value);
+            assert_eq!(*drops.lock().unwrap(), ["captured", "value"]);
         }
-    }
 
-    pub fn unpoison_with_value(&self, value: T) -> bool {
-        match self.state.compare_exchange(POISONED,
-                                          WORKING,
-                                          Ordering::AcqRel,
-                                          Ordering::Acquire) {
-            Ok(_) => {
-                let mut finish = Finish {
-                    destination_state: POISONED,
-                    state: &self.state,
-                };
-                unsafe {
-                    *self.core.get() = ThreadsafeMemoCore {
-                        func: None,
-                        value: Some(value),
-                    };
-                }
-                finish.destination_state = CALCULATED;
-                true
-            },
-            Err(_) => false,
+        #[test]
+        fn drop_unused_closure() {
+            let drops = Arc::new(Mutex::new(vec![]));
+            let memo = {
+                let captured = Counted(drops.clone(), "captured");
+                let drops = drops.clone();
+                ThreadsafeMemo::new(move || {
+                    let _captured = captured;
+                    Counted(drops, "computed")
+                })
+            };
+            assert!(memo.set(Counted(drops.clone(), "set")).is_ok());
+            assert_eq!(*drops.lock().unwrap(), ["captured"]);
+            let value = memo.take().unwrap();
+            assert_eq!(value.1, "set");
+            drop(value);
+            assert_eq!(*drops.lock().unwrap(), ["captured", "set"]);
         }
-    }
-}
 
-unsafe impl<'a, T, F: FnOnce() -> T> Sync for ThreadsafeMemo<T, F> where T: Sync, F: Sync {  }
-impl<'a, T, F: FnOnce() -> T> UnwindSafe for ThreadsafeMemo<T, F> where T: UnwindSafe, F: UnwindSafe {  }
-impl<'a, T, F: FnOnce() -> T> RefUnwindSafe for ThreadsafeMemo<T, F> where T: RefUnwindSafe, F: RefUnwindSafe {  }
+        #[test]
+        fn drop_poisoned() {
+            let drops = Arc::new(Mutex::new(vec![]));
+            let memo = {
+                let captured = Counted(drops.clone(), "captured");
+                ThreadsafeMemo::new(move || -> Counted {
+                    let _captured = captured;
+                    panic!()
+                })
+            };
+            assert!(thread::scope(|s| s.spawn(|| memo.get().map(|_| ())).join()).is_err());
+            assert!(memo.take().is_err());
+            assert_eq!(*drops.lock().unwrap(), ["captured"]);
+        }
 
-impl<'a> Drop for Finish<'a> {
-    fn drop(&mut self) {
-        let state = self.state.swap(self.destination_state, Ordering::Release);
-        assert_eq!(state & STATE_MASK, WORKING);
+        #[test]
+        fn take_or_compute_working() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            memo.state.store(WORKING | 4, Ordering::Relaxed);
+            assert_eq!(memo.take_or_compute(), Err(PoisonError));
+        }
 
-        let mut head = (state & !STATE_MASK) as *mut SpinState;
-        while !head.is_null() {
-            let spin_state = unsafe { &mut *head };
-            head = spin_state.next;
-            let thread = spin_state.thread.take().unwrap();
-            spin_state.signaled.store(true, Ordering::Release);
-            thread.unpark();
+        #[test]
+        fn after_abandoned_waiters() {
+            let (started_tx, started_rx) = channel();
+            let (release_tx, release_rx) = channel::<()>();
+            let started_tx = Mutex::new(started_tx);
+            let release_rx = Mutex::new(release_rx);
+            let memo = Arc::new(ThreadsafeMemo::new(move || {
+                started_tx.lock().unwrap().send(()).unwrap();
+                release_rx.lock().unwrap().recv().unwrap();
+                212
+            }));
+            let calculator = {
+                let memo = memo.clone();
+                thread::spawn(move || {
+                    memo.get().unwrap();
+                })
+            };
+            started_rx.recv().unwrap();
+            assert_eq!(memo.get_timeout(Duration::from_millis(10)), Ok(None));
+            release_tx.send(()).unwrap();
+            calculator.join().unwrap();
+            let memo = Arc::try_unwrap(memo).ok().unwrap();
+            assert_eq!(memo.take(), Ok(212));
         }
     }
-}
 
-#[cfg(test)]
-#[allow(unused_assignments)]
-mod tests {
-    mod new {
+    mod on_complete {
         use super::super::ThreadsafeMemo;
+        use memo::PoisonError;
+        use std::panic::{self, AssertUnwindSafe};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::mpsc::channel;
+        use std::sync::{Arc, Barrier, Mutex};
+        use std::thread;
 
         #[test]
-        fn get() {
-            let mut times = 0;
-            {
-                let memo = ThreadsafeMemo::new(|| {
-                    times += 1;
-                    212
-                });
-                assert_eq!(*memo.get().unwrap(), 212);
+        fn exactly_once() {
+            const THREADS: usize = 8;
+            let (release_tx, release_rx) = channel::<()>();
+            let release_rx = Mutex::new(release_rx);
+            let memo = Arc::new(ThreadsafeMemo::new(move || {
+                release_rx.lock().unwrap().recv().unwrap();
+                212
+            }));
+            let fired = Arc::new(AtomicUsize::new(0));
+            let barrier = Arc::new(Barrier::new(THREADS + 1));
+            let threads: Vec<_> = (0..THREADS).map(|i| {
+                let memo = memo.clone();
+                let fired = fired.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    barrier.wait();
+                    for _ in 0..100 {
+                        let fired = fired.clone();
+                        memo.on_complete(move |value| {
+                            assert_eq!(value, Ok(&212));
+                            fired.fetch_add(1, Ordering::SeqCst);
+                        });
+                    }
+                    if i % 2 == 0 {
+                        assert_eq!(*memo.get().unwrap(), 212);
+                    }
+                })
+            }).collect();
+            barrier.wait();
+            release_tx.send(()).unwrap();
+            for thread in threads {
+                thread.join().unwrap();
             }
-            assert_eq!(times, 1);
+            assert_eq!(fired.load(Ordering::SeqCst), THREADS * 100);
         }
 
         #[test]
-        fn try_get() {
-            let mut times = 0;
-            {
-                let memo = ThreadsafeMemo::new(|| {
-                    times += 1;
-                    212
-                });
-                assert!(memo.try_get().unwrap().is_none());
-            }
-            assert_eq!(times, 0);
+        fn already_finished() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            memo.get().unwrap();
+            let (tx, rx) = channel();
+            memo.on_complete(move |value| tx.send(value.cloned()).unwrap());
+            assert_eq!(rx.try_recv().unwrap(), Ok(212));
+
+            let memo: ThreadsafeMemo<u32, _> = ThreadsafeMemo::new(|| panic!());
+            let (tx, rx) = channel();
+            memo.on_complete(move |value| tx.send(value.cloned()).unwrap());
+            assert!(rx.try_recv().is_err());
+            assert!(thread::scope(|s| s.spawn(|| memo.get()).join()).is_err());
+            assert_eq!(rx.try_recv().unwrap(), Err(PoisonError));
+
+            let (tx, rx) = channel();
+            memo.on_complete(move |value| tx.send(value.cloned()).unwrap());
+            assert_eq!(rx.try_recv().unwrap(), Err(PoisonError));
         }
 
         #[test]
-        fn take() {
-            let mut times = 0;
-            {
-                let memo = ThreadsafeMemo::new(|| {
-                    times += 1;
-                    212
-                });
-                assert_eq!(memo.take().unwrap(), 212);
-            }
-            assert_eq!(times, 1);
+        fn failed_attempt_keeps_callbacks() {
+            let memo: ThreadsafeMemo<u32, fn() -> u32> = ThreadsafeMemo::new(|| 212);
+            let (tx, rx) = channel();
+            memo.on_complete(move |value| tx.send(value.cloned()).unwrap());
+            drop(memo.try_begin_compute().unwrap());
+            assert!(rx.try_recv().is_err());
+            memo.try_begin_compute().unwrap().set(12);
+            assert_eq!(rx.try_recv().unwrap(), Ok(12));
         }
 
         #[test]
-        fn try_take() {
-            let mut times = 0;
-            {
-                let memo = ThreadsafeMemo::new(|| {
-                    times += 1;
-                    212
-                });
-                assert!(memo.try_take().unwrap().is_none());
-            }
-            assert_eq!(times, 0);
+        fn get_mut() {
+            let mut memo = ThreadsafeMemo::new(|| 212);
+            let (tx, rx) = channel();
+            memo.on_complete(move |value| tx.send(value.cloned()).unwrap());
+            assert_eq!(*memo.get_mut().unwrap(), 212);
+            assert_eq!(rx.try_recv().unwrap(), Ok(212));
         }
 
         #[test]
-        fn get_get() {
-            let mut times = 0;
-            {
-                let memo = ThreadsafeMemo::new(|| {
-                    times += 1;
-                    212 + times - 1
-                });
-                assert_eq!(*memo.get().unwrap(), 212);
-                assert_eq!(*memo.get().unwrap(), 212);
-            }
-            assert_eq!(times, 1);
+        fn get_mut_poison() {
+            let mut memo = ThreadsafeMemo::new(|| -> u32 { panic!() });
+            let (tx, rx) = channel();
+            memo.on_complete(move |value| tx.send(value.cloned()).unwrap());
+            assert!(panic::catch_unwind(AssertUnwindSafe(|| memo.get_mut().is_ok())).is_err());
+            assert_eq!(rx.try_recv().unwrap(), Err(PoisonError));
+            assert!(memo.is_poisoned());
         }
 
         #[test]
-        fn get_try_get() {
-            let mut times = 0;
-            {
-                let memo = ThreadsafeMemo::new(|| {
-                    times += 1;
-                    212 + times - 1
-                });
-                assert_eq!(*memo.get().unwrap(), 212);
-                assert_eq!(*memo.try_get().unwrap().unwrap(), 212);
-            }
-            assert_eq!(times, 1);
+        fn lazy() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            memo.get().unwrap();
+            assert!(memo.callbacks.list.load(Ordering::Relaxed).is_null());
+            memo.on_complete(|_| {  });
+            assert!(!memo.callbacks.list.load(Ordering::Relaxed).is_null());
         }
 
+        // Callbacks run outside the memo's locks, so they can use it freely.
         #[test]
-        fn get_take() {
-            let mut times = 0;
+        fn reentrant() {
+            let memo = Arc::new(ThreadsafeMemo::new(|| 212));
+            let (tx, rx) = channel();
             {
-                let memo = ThreadsafeMemo::new(|| {
-                    times += 1;
-                    212 + times - 1
+                let memo2 = memo.clone();
+                memo.on_complete(move |_| {
+                    tx.send(*memo2.get().unwrap()).unwrap();
+                    memo2.on_complete(move |value| tx.send(*value.unwrap() + 1).unwrap());
                 });
-                assert_eq!(*memo.get().unwrap(), 212);
-                assert_eq!(memo.take().unwrap(), 212);
             }
-            assert_eq!(times, 1);
+            memo.get().unwrap();
+            assert_eq!(rx.try_iter().collect::<Vec<_>>(), [212, 213]);
         }
 
         #[test]
-        fn get_try_take() {
-            let mut times = 0;
+        fn panicking_callback() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            let fired = Arc::new(AtomicUsize::new(0));
+            memo.on_complete(|_| panic!());
             {
-                let memo = ThreadsafeMemo::new(|| {
-                    times += 1;
-                    212 + times - 1
+                let fired = fired.clone();
+                memo.on_complete(move |_| {
+                    fired.fetch_add(1, Ordering::SeqCst);
                 });
-                assert_eq!(*memo.get().unwrap(), 212);
-                assert_eq!(memo.try_take().unwrap().unwrap(), 212);
             }
-            assert_eq!(times, 1);
+            assert!(thread::scope(|s| s.spawn(|| memo.get().is_ok()).join()).is_err());
+            assert_eq!(fired.load(Ordering::SeqCst), 1);
+            assert_eq!(*memo.get().unwrap(), 212);
         }
     }
 
-    mod with_value {
+    mod timeout {
         use super::super::ThreadsafeMemo;
+        use std::sync::mpsc::channel;
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+        use std::time::Duration;
 
         #[test]
-        fn get() {
-            let mut memo = ThreadsafeMemo::new(|| { 200 });
-            memo = ThreadsafeMemo::with_value(212);
-            assert_eq!(*memo.get().unwrap(), 212);
-        }
-
-        #[test]
-        fn try_get() {
-            let mut memo = ThreadsafeMemo::new(|| { 200 });
-            memo = ThreadsafeMemo::with_value(212);
-            assert_eq!(*memo.try_get().unwrap().unwrap(), 212);
+        fn calculated() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            assert_eq!(*memo.get_timeout(Duration::from_millis(0)).unwrap().unwrap(), 212);
+            assert_eq!(*memo.get_timeout(Duration::from_millis(0)).unwrap().unwrap(), 212);
         }
 
         #[test]
-        fn take() {
-            let mut memo = ThreadsafeMemo::new(|| { 200 });
-            memo = ThreadsafeMemo::with_value(212);
-            assert_eq!(memo.take().unwrap(), 212);
+        fn slow() {
+            let (started_tx, started_rx) = channel();
+            let (release_tx, release_rx) = channel::<()>();
+            let release_rx = Mutex::new(release_rx);
+            let started_tx = Mutex::new(started_tx);
+            let memo = Arc::new(ThreadsafeMemo::new(move || {
+                started_tx.lock().unwrap().send(()).unwrap();
+                release_rx.lock().unwrap().recv().unwrap();
+                212
+            }));
+            let calculator = {
+                let memo = memo.clone();
+                thread::spawn(move || {
+                    assert_eq!(*memo.get().unwrap(), 212);
+                })
+            };
+            started_rx.recv().unwrap();
+            let waiters: Vec<_> = (0..6).map(|i| {
+                let memo = memo.clone();
+                thread::spawn(move || {
+                    if i % 2 == 0 {
+                        assert!(memo.get_timeout(Duration::from_millis(20)).unwrap().is_none());
+                    } else {
+                        assert_eq!(*memo.get_timeout(Duration::from_secs(60)).unwrap().unwrap(), 212);
+                    }
+                })
+            }).collect();
+            let timeouts: Vec<_> = waiters.into_iter().enumerate().filter_map(|(i, waiter)| {
+                if i % 2 == 0 {
+                    waiter.join().unwrap();
+                    None
+                } else {
+                    Some(waiter)
+                }
+            }).collect();
+            release_tx.send(()).unwrap();
+            calculator.join().unwrap();
+            for waiter in timeouts {
+                waiter.join().unwrap();
+            }
+            assert_eq!(*memo.get_timeout(Duration::from_millis(0)).unwrap().unwrap(), 212);
         }
 
         #[test]
-        fn try_take() {
-            let mut memo = ThreadsafeMemo::new(|| { 200 });
-            memo = ThreadsafeMemo::with_value(212);
-            assert_eq!(memo.try_take().unwrap().unwrap(), 212);
+        #[allow(unused_must_use)]
+        fn poison() {
+            let memo = ThreadsafeMemo::new(|| -> u32 { panic!() });
+            ::std::panic::catch_unwind(|| {
+                memo.get_timeout(Duration::from_millis(20));
+            }).unwrap_err();
+            memo.get_timeout(Duration::from_millis(20)).unwrap_err();
         }
     }
 
@@ -477,46 +3675,45 @@ mod tests {
             value: u32,
         }
 
-        impl FnOnce<()> for PoisonCallback {
-            type Output = u32;
-            extern "rust-call" fn call_once(self, _args: ()) -> Self::Output {
+        // Every call returns the same closure type, so callbacks built here can
+        // replace one another through `unpoison`.
+        fn callback(spec: PoisonCallback) -> impl FnOnce() -> u32 + RefUnwindSafe {
+            move || {
                 for _ in 0..3 {
                     thread::yield_now();
                 }
-                self.times.fetch_add(1, Ordering::SeqCst);
-                if self.panic {
+                spec.times.fetch_add(1, Ordering::SeqCst);
+                if spec.panic {
                     panic!();
                 } else {
-                    self.value
+                    spec.value
                 }
             }
         }
 
-        impl RefUnwindSafe for PoisonCallback {  }
-
         #[test]
         #[allow(unused_must_use)]
         fn unpoison() {
             let times = Arc::new(AtomicUsize::new(0));
-            let memo = ThreadsafeMemo::new(PoisonCallback {
+            let memo = ThreadsafeMemo::new(callback(PoisonCallback {
                 times: times.clone(),
                 panic: true,
                 value: 0,
-            });
-            assert!(!memo.unpoison(PoisonCallback {
+            }));
+            assert!(!memo.unpoison(callback(PoisonCallback {
                 times: times.clone(),
                 panic: false,
                 value: 0,
-            }));
+            })));
             panic::catch_unwind(|| {
                 memo.get();
             }).unwrap_err();
             memo.get().unwrap_err();
-            assert!(memo.unpoison(PoisonCallback {
+            assert!(memo.unpoison(callback(PoisonCallback {
                 times: times.clone(),
                 panic: false,
                 value: 212,
-            }));
+            })));
             assert_eq!(*memo.get().unwrap(), 212);
             assert_eq!(times.load(Ordering::SeqCst), 2);
         }
@@ -525,11 +3722,11 @@ mod tests {
         fn unpoison_race() {
             let (tx, rx) = channel();
             let times = Arc::new(AtomicUsize::new(0));
-            let memo = Arc::new(ThreadsafeMemo::new(PoisonCallback {
+            let memo = Arc::new(ThreadsafeMemo::new(callback(PoisonCallback {
                 times: times.clone(),
                 panic: true,
                 value: 0,
-            }));
+            })));
             for i in 0..12 {
                 let tx = tx.clone();
                 let memo = memo.clone();
@@ -543,10 +3740,100 @@ mod tests {
                     let mut got = memo.get();
                     let mut out = false;
                     if got.is_err() {
-                        out = memo.unpoison(PoisonCallback {
-                            times: times,
+                        out = memo.unpoison(callback(PoisonCallback {
+                            times,
                             panic: false,
                             value: 212,
+                        }));
+                        got = memo.get();
+                    }
+                    assert_eq!(*got.unwrap(), 212);
+                    tx.send(out).unwrap();
+                });
+            }
+            let mut got_one = false;
+            for _ in 0..11 {
+                let one = rx.recv().unwrap();
+                if one {
+                    if got_one {
+                        panic!();
+                    }
+                    got_one = true;
+                }
+            }
+            assert!(got_one);
+            rx.recv_timeout(Duration::from_millis(500)).unwrap_err();
+            assert_eq!(*memo.get().unwrap(), 212);
+            assert_eq!(times.load(Ordering::SeqCst), 2);
+        }
+
+        #[test]
+        #[allow(unused_must_use)]
+        fn unpoison_with() {
+            let times = Arc::new(AtomicUsize::new(0));
+            let builds = AtomicUsize::new(0);
+            let memo = ThreadsafeMemo::new(callback(PoisonCallback {
+                times: times.clone(),
+                panic: true,
+                value: 0,
+            }));
+            assert!(!memo.unpoison_with(|| {
+                builds.fetch_add(1, Ordering::SeqCst);
+                callback(PoisonCallback {
+                    times: times.clone(),
+                    panic: false,
+                    value: 0,
+                })
+            }));
+            assert_eq!(builds.load(Ordering::SeqCst), 0);
+            panic::catch_unwind(|| {
+                memo.get();
+            }).unwrap_err();
+            memo.get().unwrap_err();
+            assert!(memo.unpoison_with(|| {
+                builds.fetch_add(1, Ordering::SeqCst);
+                callback(PoisonCallback {
+                    times: times.clone(),
+                    panic: false,
+                    value: 212,
+                })
+            }));
+            assert_eq!(*memo.get().unwrap(), 212);
+            assert_eq!(builds.load(Ordering::SeqCst), 1);
+            assert_eq!(times.load(Ordering::SeqCst), 2);
+        }
+
+        #[test]
+        fn unpoison_with_race() {
+            let (tx, rx) = channel();
+            let times = Arc::new(AtomicUsize::new(0));
+            let builds = Arc::new(AtomicUsize::new(0));
+            let memo = Arc::new(ThreadsafeMemo::new(callback(PoisonCallback {
+                times: times.clone(),
+                panic: true,
+                value: 0,
+            })));
+            for i in 0..12 {
+                let tx = tx.clone();
+                let memo = memo.clone();
+                let times = times.clone();
+                let builds = builds.clone();
+                thread::spawn(move || {
+                    if i >= 6 {
+                        for _ in 0..6 {
+                            thread::yield_now();
+                        }
+                    }
+                    let mut got = memo.get();
+                    let mut out = false;
+                    if got.is_err() {
+                        out = memo.unpoison_with(|| {
+                            builds.fetch_add(1, Ordering::SeqCst);
+                            callback(PoisonCallback {
+                                times,
+                                panic: false,
+                                value: 212,
+                            })
                         });
                         got = memo.get();
                     }
@@ -567,6 +3854,7 @@ mod tests {
             assert!(got_one);
             rx.recv_timeout(Duration::from_millis(500)).unwrap_err();
             assert_eq!(*memo.get().unwrap(), 212);
+            assert_eq!(builds.load(Ordering::SeqCst), 1);
             assert_eq!(times.load(Ordering::SeqCst), 2);
         }
 
@@ -629,11 +3917,11 @@ mod tests {
         fn unpoison_and_unpoison_with_value_race() {
             let (tx, rx) = channel();
             let times = Arc::new(AtomicUsize::new(0));
-            let memo = Arc::new(ThreadsafeMemo::new(PoisonCallback {
+            let memo = Arc::new(ThreadsafeMemo::new(callback(PoisonCallback {
                 times: times.clone(),
                 panic: true,
                 value: 0,
-            }));
+            })));
             for i in 0..12 {
                 let tx = tx.clone();
                 let memo = memo.clone();
@@ -650,11 +3938,11 @@ mod tests {
                         if i & 1 == 0 {
                             out = memo.unpoison_with_value(212);
                         } else {
-                            out = memo.unpoison(PoisonCallback {
+                            out = memo.unpoison(callback(PoisonCallback {
                                 times: times.clone(),
                                 panic: false,
                                 value: 212,
-                            });
+                            }));
                         }
                         got = memo.get();
                     }
@@ -0,0 +1,102 @@
+use memo::PoisonError;
+use threadsafe_memo::ThreadsafeMemo;
+
+pub struct ThreadsafeMemoCell<T> {
+    memo: ThreadsafeMemo<T, Box<dyn FnOnce() -> T + Send>>,
+}
+
+impl<T> ThreadsafeMemoCell<T> {
+    pub fn new<F: FnOnce() -> T + Send + 'static>(func: F) -> ThreadsafeMemoCell<T> {
+        ThreadsafeMemoCell {
+            memo: ThreadsafeMemo::new(Box::new(func)),
+        }
+    }
+
+    pub fn with_value(value: T) -> ThreadsafeMemoCell<T> {
+        ThreadsafeMemoCell {
+            memo: ThreadsafeMemo::with_value(value),
+        }
+    }
+}
+
+impl<T> ThreadsafeMemoCell<T> {
+    pub fn get(&self) -> Result<&T, PoisonError> {
+        self.memo.get()
+    }
+
+    pub fn try_get(&self) -> Result<Option<&T>, PoisonError> {
+        self.memo.try_get()
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.memo.is_poisoned()
+    }
+
+    pub fn take(self) -> Result<T, PoisonError> {
+        self.memo.take()
+    }
+}
+
+// `ThreadsafeMemo` asks for `F: Sync` to be conservative, but the closure is
+// only ever moved out and called by the one thread that claims the memo, never
+// shared by reference, so `Send` is enough for it.
+unsafe impl<T: Send + Sync> Sync for ThreadsafeMemoCell<T> {  }
+
+#[cfg(test)]
+mod tests {
+    use super::ThreadsafeMemoCell;
+    use memo::PoisonError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn heterogeneous() {
+        let offset = 200;
+        let mut memos = vec![
+            ThreadsafeMemoCell::new(|| 212),
+            ThreadsafeMemoCell::new(move || offset + 12),
+        ];
+        memos.push(ThreadsafeMemoCell::with_value(666));
+        let values: Vec<i32> = memos.iter().map(|memo| *memo.get().unwrap()).collect();
+        assert_eq!(values, [212, 212, 666]);
+    }
+
+    #[test]
+    fn parallel() {
+        let times = Arc::new(AtomicUsize::new(0));
+        let memos: Arc<Vec<ThreadsafeMemoCell<usize>>> = Arc::new((0..4).map(|i| {
+            let times = times.clone();
+            if i % 2 == 0 {
+                ThreadsafeMemoCell::new(move || {
+                    times.fetch_add(1, Ordering::SeqCst);
+                    i
+                })
+            } else {
+                let name = format!("memo {}", i);
+                ThreadsafeMemoCell::new(move || {
+                    times.fetch_add(1, Ordering::SeqCst);
+                    name.len() + i
+                })
+            }
+        }).collect());
+        let threads: Vec<_> = (0..8).map(|_| {
+            let memos = memos.clone();
+            thread::spawn(move || {
+                memos.iter().map(|memo| *memo.get().unwrap()).collect::<Vec<_>>()
+            })
+        }).collect();
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), [0, 7, 2, 9]);
+        }
+        assert_eq!(times.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn poisoned() {
+        let memo: ThreadsafeMemoCell<u32> = ThreadsafeMemoCell::new(|| panic!());
+        assert!(thread::scope(|s| s.spawn(|| memo.get()).join()).is_err());
+        assert!(memo.is_poisoned());
+        assert_eq!(memo.take(), Err(PoisonError));
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::sync::{Arc, Mutex};
+use threadsafe_memo::{ThreadsafeMemo, TryError};
+
+const SHARDS: usize = 16;
+
+type Shard<K, V> = Mutex<HashMap<K, Arc<ThreadsafeMemo<V, fn() -> V>>>>;
+
+// Shard locks are only held long enough to find or insert a key's memo; the
+// memo itself makes racing callers of the same key wait for one computation.
+pub struct ThreadsafeMemoMap<K: Eq + Hash, V: Clone> {
+    hasher: RandomState,
+    shards: Vec<Shard<K, V>>,
+}
+
+impl<K: Eq + Hash, V: Clone> ThreadsafeMemoMap<K, V> {
+    pub fn new() -> ThreadsafeMemoMap<K, V> {
+        ThreadsafeMemoMap {
+            hasher: RandomState::new(),
+            shards: (0..SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> Default for ThreadsafeMemoMap<K, V> {
+    fn default() -> ThreadsafeMemoMap<K, V> {
+        ThreadsafeMemoMap::new()
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> ThreadsafeMemoMap<K, V> {
+    // Panics if the computation for this key panicked, here or on another thread.
+    pub fn get_or_compute<F: FnOnce() -> V>(&self, key: K, f: F) -> V {
+        let memo = {
+            let mut shard = self.shard(&key).lock().unwrap();
+            shard.entry(key).or_insert_with(|| Arc::new(ThreadsafeMemo::empty())).clone()
+        };
+        match memo.try_get_or_init(|| -> Result<V, ()> { Ok(f()) }) {
+            Ok(value) => value.clone(),
+            Err(TryError::Poisoned) => panic!("ThreadsafeMemoMap entry was poisoned!"),
+            Err(TryError::Failed(())) => unreachable!(),
+        }
+    }
+
+    pub fn try_get(&self, key: &K) -> Option<V> {
+        let memo = self.shard(key).lock().unwrap().get(key).cloned()?;
+        let value = memo.try_get().unwrap_or(None).cloned();
+        value
+    }
+
+    fn shard(&self, key: &K) -> &Shard<K, V> {
+        &self.shards[self.hasher.hash_one(key) as usize % SHARDS]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ThreadsafeMemoMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn get_or_compute() {
+        let memo = ThreadsafeMemoMap::new();
+        assert_eq!(memo.try_get(&0), None);
+        assert_eq!(memo.get_or_compute(0, || 212), 212);
+        assert_eq!(memo.get_or_compute(0, || 0), 212);
+        assert_eq!(memo.try_get(&0), Some(212));
+    }
+
+    #[test]
+    fn stampede() {
+        let times: Arc<Vec<AtomicUsize>> = Arc::new((0..4).map(|_| AtomicUsize::new(0)).collect());
+        let memo = Arc::new(ThreadsafeMemoMap::new());
+        let threads: Vec<_> = (0..16).map(|i| {
+            let times = times.clone();
+            let memo = memo.clone();
+            thread::spawn(move || {
+                let key = i % 4;
+                let value = memo.get_or_compute(key, || {
+                    for _ in 0..3 {
+                        thread::yield_now();
+                    }
+                    times[key].fetch_add(1, Ordering::Release);
+                    key * 100
+                });
+                assert_eq!(value, key * 100);
+            })
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        for times in times.iter() {
+            assert_eq!(times.load(Ordering::Acquire), 1);
+        }
+    }
+
+    #[test]
+    fn distinct_keys_in_parallel() {
+        let inside = Arc::new(AtomicUsize::new(0));
+        let memo = Arc::new(ThreadsafeMemoMap::new());
+        let threads: Vec<_> = (0..2).map(|key| {
+            let inside = inside.clone();
+            let memo = memo.clone();
+            thread::spawn(move || {
+                memo.get_or_compute(key, || {
+                    inside.fetch_add(1, Ordering::SeqCst);
+                    let deadline = Instant::now() + Duration::from_secs(10);
+                    while inside.load(Ordering::SeqCst) < 2 {
+                        assert!(Instant::now() < deadline, "distinct keys were serialized");
+                        thread::yield_now();
+                    }
+                    key
+                })
+            })
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+}
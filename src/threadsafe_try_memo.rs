@@ -0,0 +1,152 @@
+use memo::PoisonError;
+use threadsafe_memo::{ThreadsafeMemo, TryError};
+
+pub struct ThreadsafeTryMemo<T, E, F: FnOnce() -> Result<T, E> + Clone> {
+    func: F,
+    memo: ThreadsafeMemo<T, fn() -> T>,
+}
+
+impl<T, E, F: FnOnce() -> Result<T, E> + Clone> ThreadsafeTryMemo<T, E, F> {
+    pub fn new(func: F) -> ThreadsafeTryMemo<T, E, F> {
+        ThreadsafeTryMemo {
+            func,
+            memo: ThreadsafeMemo::empty(),
+        }
+    }
+}
+
+impl<T, E, F: FnOnce() -> Result<T, E> + Clone> ThreadsafeTryMemo<T, E, F> {
+    // Only clones the closure when there's something left to calculate.
+    pub fn get(&self) -> Result<&T, TryError<E>> {
+        match self.memo.try_get() {
+            Ok(Some(value)) => Ok(value),
+            Ok(None) => self.memo.try_get_or_init(self.func.clone()),
+            Err(PoisonError) => Err(TryError::Poisoned),
+        }
+    }
+
+    pub fn try_get(&self) -> Result<Option<&T>, PoisonError> {
+        self.memo.try_get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ThreadsafeTryMemo;
+    use threadsafe_memo::TryError;
+    use std::sync::mpsc::channel;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn get() {
+        let times = Arc::new(AtomicUsize::new(0));
+        let memo = {
+            let times = times.clone();
+            ThreadsafeTryMemo::new(move || -> Result<u32, ()> {
+                times.fetch_add(1, Ordering::SeqCst);
+                Ok(212)
+            })
+        };
+        assert!(memo.try_get().unwrap().is_none());
+        assert_eq!(*memo.get().unwrap(), 212);
+        assert_eq!(*memo.get().unwrap(), 212);
+        assert_eq!(times.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn clones() {
+        struct Counted(Arc<AtomicUsize>);
+
+        impl Clone for Counted {
+            fn clone(&self) -> Counted {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Counted(self.0.clone())
+            }
+        }
+
+        let clones = Arc::new(AtomicUsize::new(0));
+        let counted = Counted(clones.clone());
+        let memo = ThreadsafeTryMemo::new(move || -> Result<u32, ()> {
+            let _ = &counted;
+            Ok(212)
+        });
+        for _ in 0..3 {
+            assert_eq!(memo.get(), Ok(&212));
+        }
+        assert_eq!(clones.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn retry() {
+        let times = Arc::new(AtomicUsize::new(0));
+        let memo = {
+            let times = times.clone();
+            ThreadsafeTryMemo::new(move || {
+                match times.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err("failed"),
+                    _ => Ok(212),
+                }
+            })
+        };
+        assert_eq!(memo.get(), Err(TryError::Failed("failed")));
+        assert!(memo.try_get().unwrap().is_none());
+        assert_eq!(*memo.get().unwrap(), 212);
+        assert_eq!(*memo.get().unwrap(), 212);
+        assert_eq!(times.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn poison() {
+        let memo = ThreadsafeTryMemo::new(|| -> Result<u32, ()> { panic!() });
+        ::std::panic::catch_unwind(|| {
+            memo.get();
+        }).unwrap_err();
+        assert_eq!(memo.get(), Err(TryError::Poisoned));
+    }
+
+    #[test]
+    fn concurrent_retry() {
+        let (tx, rx) = channel();
+        let times = Arc::new(AtomicUsize::new(0));
+        let failures = Arc::new(AtomicUsize::new(0));
+        let memo = {
+            let times = times.clone();
+            Arc::new(ThreadsafeTryMemo::new(move || {
+                for _ in 0..3 {
+                    thread::yield_now();
+                }
+                if times.fetch_add(1, Ordering::SeqCst) < 4 {
+                    Err(())
+                } else {
+                    Ok(212)
+                }
+            }))
+        };
+        for _ in 0..12 {
+            let tx = tx.clone();
+            let memo = memo.clone();
+            let failures = failures.clone();
+            thread::spawn(move || {
+                loop {
+                    match memo.get() {
+                        Ok(value) => break tx.send(value as *const i32 as usize).unwrap(),
+                        Err(err) => {
+                            assert_eq!(err, TryError::Failed(()));
+                            failures.fetch_add(1, Ordering::SeqCst);
+                        },
+                    }
+                }
+            });
+        }
+        let first = rx.recv().unwrap();
+        for _ in 1..12 {
+            assert_eq!(rx.recv().unwrap(), first);
+        }
+        assert_eq!(times.load(Ordering::SeqCst), 5);
+        assert_eq!(failures.load(Ordering::SeqCst), 4);
+        assert_eq!(*memo.get().unwrap(), 212);
+    }
+}
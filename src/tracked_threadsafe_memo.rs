@@ -0,0 +1,113 @@
+use std::sync::Mutex;
+use std::thread;
+use memo::PoisonError;
+use threadsafe_memo::{ThreadsafeMemo, MemoState};
+
+// Remembers the name of the thread that ran the calculation, for debugging
+// stampedes. Plain `ThreadsafeMemo`s don't pay for looking the name up.
+pub struct TrackedThreadsafeMemo<T, F: FnOnce() -> T> {
+    memo: ThreadsafeMemo<T, F>,
+    calculated_by: Mutex<Option<String>>,
+}
+
+impl<T, F: FnOnce() -> T> TrackedThreadsafeMemo<T, F> {
+    pub fn new(func: F) -> TrackedThreadsafeMemo<T, F> {
+        TrackedThreadsafeMemo {
+            memo: ThreadsafeMemo::new(func),
+            calculated_by: Mutex::new(None),
+        }
+    }
+
+    pub fn with_value(value: T) -> TrackedThreadsafeMemo<T, F> {
+        TrackedThreadsafeMemo {
+            memo: ThreadsafeMemo::with_value(value),
+            calculated_by: Mutex::new(None),
+        }
+    }
+}
+
+impl<T, F: FnOnce() -> T> ThreadsafeMemo<T, F> {
+    pub fn with_thread_tracking(func: F) -> TrackedThreadsafeMemo<T, F> {
+        TrackedThreadsafeMemo::new(func)
+    }
+}
+
+impl<T, F: FnOnce() -> T> TrackedThreadsafeMemo<T, F> {
+    // The name is stored while we hold the claim, so it's in place before
+    // anyone can see the value. Nothing here hands the memo back uncalculated,
+    // so losing the claim means someone else is calculating or has finished.
+    // Calculated memos skip the claim entirely.
+    pub fn get(&self) -> Result<&T, PoisonError> {
+        if let Some(value) = self.memo.try_get()? {
+            return Ok(value);
+        }
+        match self.memo.try_begin_compute() {
+            Some(guard) => {
+                *self.calculated_by.lock().unwrap() = thread::current().name().map(str::to_owned);
+                Ok(guard.compute())
+            },
+            None => self.memo.get(),
+        }
+    }
+
+    pub fn try_get(&self) -> Result<Option<&T>, PoisonError> {
+        self.memo.try_get()
+    }
+
+    pub fn state(&self) -> MemoState {
+        self.memo.state()
+    }
+
+    pub fn calculated_by(&self) -> Option<String> {
+        match self.memo.state() {
+            MemoState::Calculated => self.calculated_by.lock().unwrap().clone(),
+            _ => None,
+        }
+    }
+
+    pub fn take(self) -> Result<T, PoisonError> {
+        self.memo.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrackedThreadsafeMemo;
+    use threadsafe_memo::ThreadsafeMemo;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn named() {
+        let memo = Arc::new(ThreadsafeMemo::with_thread_tracking(|| 212));
+        assert!(memo.calculated_by().is_none());
+        let waiters: Vec<_> = (0..4).map(|i| {
+            let memo = memo.clone();
+            thread::Builder::new().name(format!("calculator {}", i)).spawn(move || {
+                memo.get().unwrap();
+            }).unwrap()
+        }).collect();
+        for waiter in waiters {
+            waiter.join().unwrap();
+        }
+        assert_eq!(*memo.get().unwrap(), 212);
+        assert!(memo.calculated_by().unwrap().starts_with("calculator "));
+    }
+
+    #[test]
+    fn with_value() {
+        let memo: TrackedThreadsafeMemo<u32, fn() -> u32> = TrackedThreadsafeMemo::with_value(212);
+        assert_eq!(*memo.get().unwrap(), 212);
+        assert!(memo.calculated_by().is_none());
+    }
+
+    #[test]
+    fn poisoned() {
+        let memo: TrackedThreadsafeMemo<u32, _> = TrackedThreadsafeMemo::new(|| panic!());
+        let calculated = thread::scope(|s| {
+            thread::Builder::new().name("calculator".to_owned()).spawn_scoped(s, || memo.get().is_ok()).unwrap().join()
+        });
+        assert!(calculated.is_err());
+        assert!(memo.calculated_by().is_none());
+    }
+}
@@ -0,0 +1,110 @@
+use memo::Memo;
+use aliasable_memo::AliasableMemo;
+#[cfg(feature = "std")]
+use threadsafe_memo::ThreadsafeMemo;
+#[cfg(feature = "std")]
+use blocker::Blocker;
+
+pub trait Lazy {
+    type Value;
+
+    fn try_peek(&self) -> Option<&Self::Value>;
+}
+
+// `Memo` calculates through `&mut self`, so only the interior-mutable memos
+// can be forced through a shared reference.
+pub trait LazyShared: Lazy {
+    fn get(&self) -> Option<&Self::Value>;
+}
+
+impl<T, F: FnOnce() -> T> Lazy for Memo<T, F> {
+    type Value = T;
+
+    fn try_peek(&self) -> Option<&T> {
+        self.try_get()
+    }
+}
+
+impl<T, F: FnOnce() -> T> Lazy for AliasableMemo<T, F> {
+    type Value = T;
+
+    fn try_peek(&self) -> Option<&T> {
+        self.try_get()
+    }
+}
+
+impl<T, F: FnOnce() -> T> LazyShared for AliasableMemo<T, F> {
+    fn get(&self) -> Option<&T> {
+        Some(AliasableMemo::get(self))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T, F: FnOnce() -> T, B: Blocker> Lazy for ThreadsafeMemo<T, F, B> {
+    type Value = T;
+
+    fn try_peek(&self) -> Option<&T> {
+        self.value()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T, F: FnOnce() -> T, B: Blocker> LazyShared for ThreadsafeMemo<T, F, B> {
+    fn get(&self) -> Option<&T> {
+        ThreadsafeMemo::get(self).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod lazy {
+        use super::super::{Lazy, LazyShared};
+        use memo::Memo;
+        use aliasable_memo::AliasableMemo;
+        use threadsafe_memo::ThreadsafeMemo;
+        use std::panic;
+
+        fn peek<L: Lazy<Value = u32>>(lazy: &L) -> Option<u32> {
+            lazy.try_peek().cloned()
+        }
+
+        fn force<L: LazyShared<Value = u32>>(lazy: &L) -> Option<u32> {
+            lazy.get().cloned()
+        }
+
+        #[test]
+        fn memo() {
+            let mut memo = Memo::new(|| 212);
+            assert_eq!(peek(&memo), None);
+            memo.get();
+            assert_eq!(peek(&memo), Some(212));
+        }
+
+        #[test]
+        fn aliasable_memo() {
+            let memo = AliasableMemo::new(|| 212);
+            assert_eq!(peek(&memo), None);
+            assert_eq!(force(&memo), Some(212));
+            assert_eq!(peek(&memo), Some(212));
+        }
+
+        #[test]
+        fn threadsafe_memo() {
+            let memo = ThreadsafeMemo::new(|| 212);
+            assert_eq!(peek(&memo), None);
+            assert_eq!(force(&memo), Some(212));
+            assert_eq!(peek(&memo), Some(212));
+        }
+
+        #[test]
+        #[allow(unused_must_use)]
+        fn threadsafe_memo_poisoned() {
+            let memo = ThreadsafeMemo::new(|| -> u32 { panic!() });
+            panic::catch_unwind(|| {
+                memo.get();
+            }).unwrap_err();
+            assert_eq!(peek(&memo), None);
+            assert_eq!(force(&memo), None);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+extern crate memo;
+
+use memo::{AliasableMemo, Blocker, Memo, ThreadsafeMemo, ThreadsafeMemoCell};
+use std::cell::Cell;
+use std::marker::{PhantomData, PhantomPinned};
+use std::rc::Rc;
+use std::sync::Arc;
+
+fn assert_send<T: Send>() {  }
+fn assert_sync<T: Sync>() {  }
+
+// Fails to compile if `$ty` implements `$trait`: the call can then pick either
+// impl and the placeholder can't be inferred. Unlike a compile-fail snapshot,
+// this doesn't depend on the compiler's wording.
+macro_rules! assert_not_impl {
+    ($ty:ty: $trait:path) => {{
+        trait AmbiguousIfImpl<A> {
+            fn some_item() {  }
+        }
+
+        impl<T: ?Sized> AmbiguousIfImpl<()> for T {  }
+
+        #[allow(dead_code)]
+        struct Invalid;
+
+        impl<T: ?Sized + $trait> AmbiguousIfImpl<Invalid> for T {  }
+
+        let _ = <$ty as AmbiguousIfImpl<_>>::some_item;
+    }};
+}
+
+// Never actually blocks; only here for its auto traits.
+#[derive(Clone)]
+struct LocalBlocker(PhantomData<Rc<()>>);
+
+impl Blocker for LocalBlocker {
+    fn current() -> LocalBlocker {
+        LocalBlocker(PhantomData)
+    }
+
+    fn block(&self) {  }
+
+    fn unblock(&self) {  }
+}
+
+// `AliasableMemo` mutates through `&self` with no synchronization, so it must
+// never be `Sync`; `Memo` and `ThreadsafeMemo` only inherit their auto traits
+// from the value and closure types.
+#[test]
+fn memo() {
+    // `Memo` only calculates through `&mut self`, so sharing it is harmless.
+    assert_send::<Memo<u32, fn() -> u32>>();
+    assert_sync::<Memo<u32, fn() -> u32>>();
+    assert_not_impl!(Memo<Rc<u32>, fn() -> Rc<u32>>: Send);
+    assert_not_impl!(Memo<PhantomPinned, fn() -> PhantomPinned>: Unpin);
+}
+
+#[test]
+fn aliasable_memo() {
+    assert_send::<AliasableMemo<u32, fn() -> u32>>();
+    assert_not_impl!(AliasableMemo<u32, fn() -> u32>: Sync);
+}
+
+#[test]
+fn threadsafe_memo() {
+    assert_send::<ThreadsafeMemo<u32, fn() -> u32>>();
+    assert_sync::<ThreadsafeMemo<u32, fn() -> u32>>();
+    assert_sync::<ThreadsafeMemo<Arc<String>, Box<dyn FnOnce() -> Arc<String> + Send + Sync>>>();
+    assert_not_impl!(ThreadsafeMemo<Cell<u32>, fn() -> Cell<u32>>: Sync);
+    assert_not_impl!(ThreadsafeMemo<u32, Box<dyn FnOnce() -> u32 + Send>>: Sync);
+    // waiters' blockers cross threads, and so would the memo's
+    assert_not_impl!(ThreadsafeMemo<u32, fn() -> u32, LocalBlocker>: Send);
+    assert_not_impl!(ThreadsafeMemo<u32, fn() -> u32, LocalBlocker>: Sync);
+}
+
+#[test]
+fn threadsafe_memo_cell() {
+    // only needs the boxed closure to be `Send`
+    assert_send::<ThreadsafeMemoCell<u32>>();
+    assert_sync::<ThreadsafeMemoCell<u32>>();
+}
@@ -0,0 +1,111 @@
+// Exhaustively checks the interleavings of `ThreadsafeMemo`'s state machine.
+// Only meaningful with loom's models swapped in, and slow without
+// optimizations:
+//
+//     cargo test --release --features loom --test loom
+#![cfg(feature = "loom")]
+
+extern crate loom;
+extern crate memo;
+
+use loom::sync::atomic::{AtomicUsize, Ordering};
+use loom::sync::Arc;
+use loom::thread;
+use memo::{PoisonError, ThreadsafeMemo};
+use std::panic::{self, AssertUnwindSafe};
+
+// Every thread that gets at once must see the one value a single calculation
+// produced, whether it calculates, queues behind the calculation or arrives
+// after it.
+#[test]
+fn stampede() {
+    loom::model(|| {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let memo = {
+            let runs = runs.clone();
+            Arc::new(ThreadsafeMemo::new(move || {
+                runs.fetch_add(1, Ordering::Relaxed);
+                212
+            }))
+        };
+        let threads: Vec<_> = (0..2).map(|_| {
+            let memo = memo.clone();
+            thread::spawn(move || *memo.get().unwrap())
+        }).collect();
+        assert_eq!(*memo.get().unwrap(), 212);
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), 212);
+        }
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+    });
+}
+
+// A panicking calculation must wake whoever is queued behind it, and nobody
+// may see a value.
+#[test]
+fn poison() {
+    loom::model(|| {
+        let memo: Arc<ThreadsafeMemo<u32, _>> = Arc::new(ThreadsafeMemo::new(|| panic!()));
+        let waiter = {
+            let memo = memo.clone();
+            thread::spawn(move || panic::catch_unwind(AssertUnwindSafe(|| memo.get().copied())))
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| memo.get().copied()));
+        // whichever thread calculated unwound; the other saw the poison
+        let results = [result, waiter.join().unwrap()];
+        assert_eq!(results.iter().filter(|result| result.is_err()).count(), 1);
+        assert!(results.iter().any(|result| matches!(*result, Ok(Err(PoisonError)))));
+        assert!(memo.is_poisoned());
+    });
+}
+
+// Only one of several racing unpoisons may win, and a concurrent `get` sees
+// either the poison or the new value, never anything in between.
+#[test]
+fn unpoison() {
+    fn poisoned() -> u32 {
+        panic!()
+    }
+
+    fn fixed() -> u32 {
+        212
+    }
+
+    loom::model(|| {
+        let memo: Arc<ThreadsafeMemo<u32, fn() -> u32>> = Arc::new(ThreadsafeMemo::new(poisoned));
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| memo.get().is_ok())).is_err());
+        let unpoisons: Vec<_> = (0..2).map(|_| {
+            let memo = memo.clone();
+            thread::spawn(move || memo.unpoison(fixed))
+        }).collect();
+        if let Ok(value) = memo.get() {
+            assert_eq!(*value, 212);
+        }
+        let won = unpoisons.into_iter().map(|thread| thread.join().unwrap()).filter(|&won| won).count();
+        assert_eq!(won, 1);
+        assert_eq!(*memo.get().unwrap(), 212);
+    });
+}
+
+// A callback registered while another thread calculates must run exactly
+// once, either from the finishing thread or right away.
+#[test]
+fn on_complete() {
+    loom::model(|| {
+        let fired = Arc::new(AtomicUsize::new(0));
+        let memo = Arc::new(ThreadsafeMemo::new(|| 212));
+        let calculator = {
+            let memo = memo.clone();
+            thread::spawn(move || *memo.get().unwrap())
+        };
+        {
+            let fired = fired.clone();
+            memo.on_complete(move |value| {
+                assert_eq!(value, Ok(&212));
+                fired.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+        assert_eq!(calculator.join().unwrap(), 212);
+        assert_eq!(fired.load(Ordering::Relaxed), 1);
+    });
+}
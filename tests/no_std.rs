@@ -0,0 +1,31 @@
+// Exercises the memos that only need `core`, from a crate that can't reach
+// `std` itself:
+//
+//     cargo test --no-default-features --test no_std
+#![no_std]
+
+extern crate memo;
+
+use memo::{Memo, AliasableMemo, RefCellMemo};
+
+#[test]
+fn memo() {
+    let mut memo = Memo::new(|| 212);
+    assert_eq!(memo.try_get(), None);
+    assert_eq!(*memo.get(), 212);
+    assert_eq!(memo.get_checked(), Ok(&212));
+}
+
+#[test]
+fn aliasable_memo() {
+    let memo = AliasableMemo::new(|| 212);
+    assert_eq!(*memo.get(), 212);
+    assert_eq!(memo.try_get(), Some(&212));
+}
+
+#[test]
+fn ref_cell_memo() {
+    let memo = RefCellMemo::new(|| 212);
+    assert_eq!(*memo.get(), 212);
+    assert!(memo.try_get().is_some());
+}